@@ -2,18 +2,27 @@ use crate::{
     command,
     core::{
         error::{ASCmdError, ASGameError, CommandErrors},
-        ASType, ASVariable, GameInfo,
+        ASEvent, ASType, ASVariable, GameInfo,
     },
     formats::save,
-    unwrap_var,
+    parsing, unwrap_var,
 };
 use anyhow;
 use std::{collections::HashMap, iter::FromIterator};
 
+/// What a command runs once its arguments are checked
+#[derive(Clone, Copy)]
+pub enum CommandFn {
+    /// A command written in Rust
+    Native(fn(&mut GameInfo, HashMap<String, ASVariable>) -> anyhow::Result<()>),
+    /// A function defined in the script with `!def`, by the lines its body starts and ends in
+    Script { start: i64, end: i64 },
+}
+
 #[derive(Clone)]
 pub struct Command {
     pub name: String,
-    func: fn(&mut GameInfo, HashMap<String, ASVariable>) -> anyhow::Result<()>,
+    func: CommandFn,
     args_to_kwargs: Vec<String>,
     accepted_kwargs: HashMap<String, ASType>,
     default_values: HashMap<String, ASVariable>,
@@ -27,10 +36,16 @@ pub struct CmdSet {
     pub modules: HashMap<String, CmdSet>, //TODO: make it work
 }
 
+impl Default for CmdSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CmdSet {
     pub fn get(&self, name: &str) -> Option<&Command> {
         let (module, name) = name.split_once(".").unwrap_or(("", name));
-        let module = if module != "" {
+        let module = if !module.is_empty() {
             self.modules.get(module)?
         } else {
             self
@@ -83,23 +98,48 @@ impl Command {
         //TODO: make sure arg ordering/defaults are well done
         Self {
             name,
-            func,
+            func: CommandFn::Native(func),
             args_to_kwargs,
             accepted_kwargs,
             default_values,
             deprecated,
         }
     }
+    /// Creates a command for a function defined with `!def`, which takes the given parameters
+    /// (of any type) and runs the script lines from `start` up to (not including) `end`
+    pub fn function(name: String, params: Vec<String>, start: i64, end: i64) -> Self {
+        Self {
+            name,
+            func: CommandFn::Script { start, end },
+            accepted_kwargs: params
+                .iter()
+                .map(|c| (c.to_string(), ASType::Any))
+                .collect(),
+            args_to_kwargs: params,
+            default_values: HashMap::new(),
+            deprecated: false,
+        }
+    }
     pub fn run(
         &self,
         info: &mut GameInfo,
         args: Vec<ASVariable>,
         kwargs: HashMap<String, ASVariable>,
     ) -> anyhow::Result<()> {
-        let mut c = 0;
+        self.call(info, args, kwargs)?;
+        Ok(())
+    }
+    /// Same as [Command::run], but gets the value returned by the command. Only functions
+    /// defined in scripts can return values, other commands always return `None`.
+    pub fn call(
+        &self,
+        info: &mut GameInfo,
+        args: Vec<ASVariable>,
+        kwargs: HashMap<String, ASVariable>,
+    ) -> anyhow::Result<ASVariable> {
         let mut kwargs = kwargs;
         // Turn positional arguments into keyword arguments
-        for arg in &args {
+        for (c, arg) in args.iter().enumerate() {
             let argname = match self.args_to_kwargs.get(c) {
                 None => Err(ASCmdError {
                     command: String::from(&self.name),
@@ -111,7 +151,6 @@ impl Command {
                 Some(c) => Ok(c),
             }?;
             kwargs.insert(String::from(argname), arg.to_owned());
-            c += 1;
         }
         // Pass default argument values
         for (key, value) in &self.default_values {
@@ -199,8 +238,24 @@ impl Command {
             info.warn(format!("Command '{}' is deprecated", self.name));
         }
 
-        (self.func)(info, kwargs)
+        match self.func {
+            CommandFn::Native(func) => {
+                func(info, kwargs)?;
+                Ok(ASVariable::None)
+            }
+            CommandFn::Script { start, end } => parsing::run_function(info, start, end, kwargs),
+        }
+    }
+}
+
+/// Restores the latest save from a command. Returns whether there was one.
+fn continue_game(info: &mut GameInfo) -> anyhow::Result<bool> {
+    let restored = save::restore_latest(info)?;
+    if restored {
+        // the main loop moves to the next line after the command, so this runs the restored line
+        info.pointer -= 1;
     }
+    Ok(restored)
 }
 
 pub fn main_commands() -> CmdSet {
@@ -230,7 +285,7 @@ pub fn main_commands() -> CmdSet {
                     // separate the choices into the vectors defined above
                     while c <= 9 {
                         let choice = unwrap_var!(kwargs -> &format!("choice{}", c); List)?;
-                        let text = match choice.get(0) {
+                        let text = match choice.first() {
                             Some(s) => match s {
                                 ASVariable::String(c) => c.to_string(),
                                 ASVariable::VarRef {name, flag} => {
@@ -336,9 +391,13 @@ pub fn main_commands() -> CmdSet {
             command! {
                 ending (name: String = "".to_string(), ) => |info, kwargs| {
                     let name = unwrap_var!(kwargs -> "name"; String)?;
-                    info.show(&format!("Ending: {}", name))?;
-                    info.quit();
-                    Ok(())
+                    match info.reach_ending(name)? {
+                        Some(ending) => info.show(&format!("Ending: {}", ending.title))?,
+                        None => info.show(&format!("Ending: {}", name))?,
+                    }
+                    info.emit(ASEvent::EndingReached(name.to_string()));
+                    info.module_ending(name)?;
+                    info.end_playthrough(true)
                 }
             },
             command! {
@@ -369,8 +428,9 @@ pub fn main_commands() -> CmdSet {
             command! {
                 add (!var: VarRef, !value: Any,) => |info, kwargs| {
                     let var = kwargs.get("var").unwrap();
-                    let val = info.get_var(var)?.clone();
-                    info.set_var(var, (val + kwargs.get("value").unwrap().clone())?)
+                    let val = (info.get_var(var)?.clone() + kwargs.get("value").unwrap().clone())?;
+                    info.limits.check_size(&val)?;
+                    info.set_var(var, val)
                 }
             },
             command! {
@@ -401,17 +461,110 @@ pub fn main_commands() -> CmdSet {
                     Ok(())
                 }
             },
+            command! {
+                undo (!val: Bool, ) => |info, kwargs| {
+                    info.allow_undo = *unwrap_var!(kwargs -> "val"; Bool)?;
+                    // choices made before undoing was turned off stay locked in
+                    if !info.allow_undo {
+                        info.history.clear();
+                    }
+                    Ok(())
+                }
+            },
+            command! {
+                checkpoint () => |info, _kwargs| {
+                    save::autosave(info)
+                }
+            },
             command! {
                 gameover => |info, _kwargs| {
                     info.show("**GAME OVER**")?;
-                    let query = info.query("Start over from last save?", vec!("Yes","No"))?;
-                    if query == 1 {
-                        if !save::restore(info)? {
-                            info.quit();
-                        };
+                    let query = info.menu("Start over from last save?", vec!("Yes","No"))?;
+                    if query == 1 && continue_game(info)? {
+                        return Ok(());
+                    }
+                    info.end_playthrough(false)
+                }
+            },
+            // not made with command! since it can't have Any arguments with default values
+            Command::new(
+                "return".to_string(),
+                |info, kwargs| {
+                    if info.locals.is_empty() {
+                        Err(ASCmdError {
+                            command: "return".to_string(),
+                            details: CommandErrors::ReturnOutsideFunction,
+                        })?
+                    }
+                    info.returning = Some(kwargs.get("value").unwrap().clone());
+                    Ok(())
+                },
+                vec!["value".to_string()],
+                HashMap::from([("value".to_string(), ASType::Any)]),
+                HashMap::from([("value".to_string(), ASVariable::None)]),
+                false,
+            ),
+            command! {
+                local (!var: VarRef, !value: Any,) => |info, kwargs| {
+                    let mut val = kwargs.get("value").unwrap().clone();
+                    while val.get_type() == ASType::VarRef {
+                        val = info.get_var(&val)?.clone();
+                    }
+                    match kwargs.get("var").unwrap() {
+                        ASVariable::VarRef { name, flag: false } => info.set_local(name, val),
+                        _ => Err(ASCmdError {
+                            command: "local".to_string(),
+                            details: CommandErrors::Generic {
+                                details: "Flags can't be local".to_string(),
+                            },
+                        })?,
+                    }
+                }
+            },
+            command! {
+                achieve (!id: String,) => |info, kwargs| {
+                    let id = unwrap_var!(kwargs -> "id"; String)?;
+                    info.unlock_achievement(id)?;
+                    Ok(())
+                }
+            },
+            command! {
+                achievements () => |info, _kwargs| {
+                    info.show_achievements()
+                }
+            },
+            command! {
+                endings () => |info, _kwargs| {
+                    info.show_endings()
+                }
+            },
+            command! {
+                newgame (plus: Bool = false, ) => |info, kwargs| {
+                    if *unwrap_var!(kwargs -> "plus"; Bool)? {
+                        info.new_game_plus()?;
                     } else {
-                        info.quit();
+                        info.new_game()?;
                     }
+                    // the main loop moves to the next line after running a command
+                    info.pointer -= 1;
+                    Ok(())
+                }
+            },
+            command! {
+                continue () => |info, _kwargs| {
+                    continue_game(info)?;
+                    Ok(())
+                }
+            },
+            command! {
+                quit () => |info, _kwargs| {
+                    info.quit();
+                    Ok(())
+                }
+            },
+            command! {
+                do (!value: Any,) => |_info, _kwargs| {
+                    // the value is already evaluated when parsing the arguments
                     Ok(())
                 }
             },
@@ -439,8 +592,7 @@ pub fn main_commands() -> CmdSet {
                         })?
                     }
 
-                    let mut c = 0; // counter
-                    for value in values {
+                    for (c, value) in values.iter().enumerate() {
                         let mut value = value.clone();
                         while value.get_type() == ASType::VarRef {
                             value = info.get_var(&value)?.clone();
@@ -461,22 +613,33 @@ pub fn main_commands() -> CmdSet {
                             info.goto_label(label)?;
                             return Ok(())
                         }
-                        c += 1;
                     }
                     info.goto_label(default)
                 }
             },
             command! {
                 append (!list: VarRef, !val: Any,) => |info, kwargs| {
-                    match info.get_var_mut(kwargs.get("list").unwrap())? {
+                    let var = kwargs.get("list").unwrap();
+                    let val = kwargs.get("val").unwrap();
+                    // changed on a copy, so it's left as it was if it gets too big
+                    let mut value = info.get_var(var)?.clone();
+                    match &mut value {
                         ASVariable::List(list) => {
-                            let val = kwargs.get("val").unwrap();
                             list.push(val.clone());
-                            Ok(())
-                        }
-                        ASVariable::Map(map) => {
-                            todo!()
                         }
+                        // maps take the entries of another map, since a single value has no key
+                        ASVariable::Map(map) => match val {
+                            ASVariable::Map(entries) => map.extend(entries.clone()),
+                            _ => Err(ASCmdError {
+                                command: "append".to_string(),
+                                details: CommandErrors::Generic {
+                                    details: format!(
+                                        "only a Map can be appended to a Map, but type {} was given; use `!do map.insert(key, value)` to add a single entry",
+                                        val.get_type()
+                                    ),
+                                },
+                            })?,
+                        },
                         _ => Err(ASCmdError {
                             command: "append".to_string(),
                             details: CommandErrors::Generic {
@@ -484,6 +647,9 @@ pub fn main_commands() -> CmdSet {
                             },
                         })?,
                     }
+                    info.limits.check_size(&value)?;
+                    // set through set_var, so the change is notified (and stored, if it's persistent)
+                    info.set_var(var, value)
                 }
             },
         ],
@@ -1,11 +1,11 @@
-use crate::core::{ASType, GameInfo, KeyVar};
+use crate::core::{ASEvent, ASType, GameInfo, KeyVar};
 use std::{
     error::Error,
     fmt::{Display, Formatter, Result},
 };
 use thiserror::Error;
 
-pub(crate) fn manage_error(info: &GameInfo, err: anyhow::Error) {
+pub(crate) fn manage_error(info: &mut GameInfo, err: anyhow::Error) {
     let mut error = format!(
         "\nAdventureScript error on script {}, line {} - ",
         info.script_name(),
@@ -20,6 +20,8 @@ pub(crate) fn manage_error(info: &GameInfo, err: anyhow::Error) {
         error += "feature not implemented:\n    ";
     } else if let Some(_c) = err.downcast_ref::<ASVarError>() {
         error += "variable error:\n    ";
+    } else if let Some(_c) = err.downcast_ref::<ASLimitError>() {
+        error += "resource limit exceeded:\n    ";
     } else if let Some(_c) = err.downcast_ref::<ASGameError>() {
         error += "error raised by game:\n    ";
     } else if let Some(_c) = err.downcast_ref::<ASOtherError>() {
@@ -27,19 +29,17 @@ pub(crate) fn manage_error(info: &GameInfo, err: anyhow::Error) {
     } else {
         error += "uncaught internal error:\n    ";
     };
-    info.error(format!(
-        "{}{}",
-        error,
-        (|| {
-            let err = err.to_string();
-            let mut lines = err.lines();
-            let mut out = lines.next().unwrap().to_string();
-            for line in lines {
-                out += &format!("\n    {}", line);
-            }
-            out
-        })()
-    ));
+    let message = format!("{}{}", error, {
+        let err = err.to_string();
+        let mut lines = err.lines();
+        let mut out = lines.next().unwrap().to_string();
+        for line in lines {
+            out += &format!("\n    {}", line);
+        }
+        out
+    });
+    info.emit(ASEvent::ErrorRaised(message.clone()));
+    info.error(message);
 }
 
 // Command error
@@ -115,6 +115,12 @@ pub enum CommandErrors {
     SwitchLabelType { number: usize, given: ASType },
     #[error("!switch command was given a different number of values ({0}) and labels ({1})")]
     SwitchParams(usize, usize),
+    #[error("!return can only be used inside a function defined with !def")]
+    ReturnOutsideFunction,
+    #[error("Achievement {0} doesn't exist - check the game's achievements file")]
+    UnknownAchievement(String),
+    #[error("Ending {0} doesn't exist - check the endings in info.toml")]
+    UnknownEnding(String),
 }
 
 // Same thing but for methods
@@ -160,6 +166,8 @@ pub enum MethodErrors {
         required_type: ASType,
         given_type: ASType,
     },
+    #[error("Method modifies the value it's called on, so it can only be used on variables")]
+    NotAVariable,
 }
 
 // File error
@@ -210,10 +218,28 @@ pub enum FileErrors {
     ConfigLoadError(String),
     #[error("Error parsing save file:\n    {0}")]
     SaveLoadError(String),
+    #[error("Error writing save file:\n    {0}")]
+    SaveWriteError(String),
     #[error("Save was created on incompatible AdventureScript version v{0}")]
     SaveNotCompatible(String),
     #[error("Save was created on a newer version of AdventureScript (v{0})")]
     SaveTooNew(String),
+    #[error("Save uses version {0} of the save format, which is newer than this version of AdventureScript supports")]
+    SaveSchemaTooNew(u64),
+    #[error("Save was created on a newer version of the game (v{0})")]
+    SaveGameTooNew(String),
+    #[error("Save was modified outside of the game")]
+    SaveModified,
+    #[error(
+        "Game files can't be written to when the game is loaded from an archive or from memory"
+    )]
+    ReadOnlySource,
+    #[error("Error reading game archive:\n    {0}")]
+    ArchiveLoadError(String),
+    #[error("Files can only be accessed inside the game's folder or the save folder")]
+    ForbiddenPath,
+    #[error("Save slot name '{0}' is invalid - use only letters, numbers, '-' and '_'")]
+    InvalidSlot(String),
 }
 
 // Syntax/parsing error
@@ -255,16 +281,7 @@ pub enum ASSyntaxError {
     #[error("Label {0} doesn't exist")]
     NonExistentLabel(String),
     #[error("Label {0} is defined multiple times in the following lines: {}",
-        ( |vec: &Vec<i64>| {
-            let mut out = "".to_string();
-            for elmt in vec {
-                if out != "" {
-                    out += ", ";
-                }
-                out += &elmt.to_string();
-            }
-            out
-        } ) (.1)
+        .1.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(", ")
     )]
     RepeatedLabel(String, Vec<i64>),
     #[error("Syntax error at '{0}'")]
@@ -273,6 +290,16 @@ pub enum ASSyntaxError {
     InvalidMethod(String),
     #[error("Method {0} doesn't exist for type {1}")]
     UnknownMethod(String, ASType),
+    #[error("Invalid !struct declaration: {0}\nTip: use !struct Name; field: Type; other_field: Type = value")]
+    InvalidStruct(String),
+    #[error("Type {0} doesn't exist")]
+    UnknownType(String),
+    #[error("Function {0} is missing its !end line")]
+    UnclosedFunction(String),
+    #[error("Invalid !def declaration: {0}\nTip: use !def name(parameter, other_parameter)")]
+    InvalidFunction(String),
+    #[error("Function {0} has the same name as an existing command")]
+    FunctionIsCommand(String),
 }
 
 // Error for WIP/unimplemented stuff
@@ -302,6 +329,53 @@ pub enum ASVarError {
     WrongListIndex { num_items: usize, index: i64 },
     #[error("Tried to get value of a map associated to key {key}, which doesn't exist")]
     WrongMapKey { key: KeyVar },
+    #[error("Expected a value of type {expected}, but got {given}")]
+    WrongType { expected: ASType, given: ASType },
+    #[error("Module global {name} is of type {expected}, but was given a value of type {given}")]
+    GlobalWrongType {
+        name: String,
+        expected: ASType,
+        given: ASType,
+    },
+    #[error("Tried to delete module global {0}, but module globals can't be deleted")]
+    DeleteGlobal(String),
+    #[error("Object type {0} doesn't exist")]
+    UnknownObject(String),
+    #[error("Objects of type {spec} don't have a field named {field}")]
+    UnknownField { spec: String, field: String },
+    #[error(
+        "Field {field} of type {spec} is of type {expected}, but was given a value of type {given}"
+    )]
+    FieldWrongType {
+        spec: String,
+        field: String,
+        expected: ASType,
+        given: ASType,
+    },
+    #[error("Tried to access a field of {name}, but it's of type {type_}, not an object")]
+    NotAnObject { name: String, type_: ASType },
+    #[error("Tried to delete object field {0}, but fields can't be deleted")]
+    DeleteField(String),
+    #[error("Tried to change or delete {0}, but it's a constant")]
+    ConstAssign(String),
+    #[error("Tried to declare local variable {0}, but there's a constant with that name")]
+    LocalIsConst(String),
+    #[error("Constant {0} is declared more than once with different values")]
+    ConstRedeclared(String),
+}
+
+//Resource limit error
+
+#[derive(Debug, Error)]
+pub enum ASLimitError {
+    #[error("Ran {0} lines without any player interaction - is there an infinite loop?")]
+    TooManySteps(u64),
+    #[error("Tried to create a value of size {size}, but the maximum is {max}")]
+    ValueTooBig { size: usize, max: usize },
+    #[error("Calls are nested more than {0} levels deep")]
+    CallDepth(usize),
+    #[error("Expression has more than {0} levels of nested brackets")]
+    TooDeep(usize),
 }
 
 //Error raised from the game
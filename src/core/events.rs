@@ -0,0 +1,58 @@
+use crate::core::{ASVariable, GameInfo};
+
+/// Something that happened while running the game, which host applications and modules can
+/// react to by registering an [EventListener].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ASEvent {
+    /// The game reached a label, either by jumping to it or by reaching its line
+    LabelEntered(String),
+    /// A variable or flag was given a new value
+    VarChanged {
+        name: String,
+        flag: bool,
+        value: ASVariable,
+    },
+    /// A variable or flag was deleted
+    VarDeleted { name: String, flag: bool },
+    /// A script file was loaded, by name
+    ScriptLoaded(String),
+    /// A choice from the game was shown to the player. Engine menus, like the title screen,
+    /// don't emit choice events.
+    ChoicePresented { text: String, choices: Vec<String> },
+    /// The player picked a choice. The number starts at 1, like in the shown choices.
+    ChoiceMade { number: u8, text: String },
+    /// The game was saved
+    SaveWritten,
+    /// A save was restored
+    SaveRestored,
+    /// The player went back the given number of choices
+    ChoicesUndone(usize),
+    /// The game reached an ending, by name
+    EndingReached(String),
+    /// An achievement was unlocked for the first time, by ID
+    AchievementUnlocked(String),
+    /// The game was stopped by an error, with the same message shown to the player
+    ErrorRaised(String),
+}
+
+/// A function that gets called for every [ASEvent]. It can read the state of the game, but not
+/// modify it.
+pub type EventListener = Box<dyn FnMut(&GameInfo, &ASEvent)>;
+
+impl GameInfo {
+    /// Registers a function to be called on every [ASEvent]
+    pub fn add_listener(&mut self, listener: EventListener) {
+        self.listeners.push(listener);
+    }
+
+    /// Notifies every registered listener of an event
+    pub fn emit(&mut self, event: ASEvent) {
+        // the listeners are taken out so they can get a reference to the rest of the GameInfo
+        let mut listeners = std::mem::take(&mut self.listeners);
+        for listener in &mut listeners {
+            listener(self, &event);
+        }
+        listeners.append(&mut self.listeners);
+        self.listeners = listeners;
+    }
+}
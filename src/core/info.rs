@@ -1,16 +1,30 @@
 use crate::{
     core::{
         error::{ASOtherError, ASSyntaxError, ASVarError},
-        ASType, ASVariable, AdventureIO, FileType,
+        ASEvent, ASFile, ASType, ASVariable, AdventureIO, CmdSet, EventListener, FileType,
+        GameSource, Limits,
     },
-    formats::{config, config::Config, save},
-    modules::ObjSpec,
+    formats::{
+        achievements::Achievement,
+        config,
+        config::Config,
+        persistent,
+        save::{self, Save},
+    },
+    modules::{ModuleHooks, ObjSpec},
+    parsing,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::TryFrom,
+    io::Read,
+    rc::Rc,
+    time::{Duration, Instant},
 };
-use std::{collections::HashMap, fs::File, io::Read, path::PathBuf};
 
 pub struct GameInfo {
     pub io: AdventureIO,
-    pub root_dir: PathBuf,
+    pub source: GameSource,
     pub script_name: String,
     script: Vec<String>,
     pub pointer: i64,
@@ -21,16 +35,56 @@ pub struct GameInfo {
     pub local: bool,
     pub debug: bool,
     pub allow_save: bool,
+    /// Whether choices can be undone right now, set with `!undo`
+    pub allow_undo: bool,
     pub screentext: String,
     pub objects: Vec<ObjSpec>,
-    pub mod_globals: HashMap<String, ASVariable>, //TODO: add to save
+    /// `!struct` declarations that have been run, so they can be stored in saves
+    pub(crate) structs: Vec<String>,
+    /// Commands available to the game, used when running functions defined in scripts
+    pub(crate) commands: Rc<CmdSet>,
+    /// Functions defined in the current script with `!def`
+    pub(crate) functions: Rc<CmdSet>,
+    /// Parameters and local variables of the functions currently running, innermost last
+    pub(crate) locals: Vec<HashMap<String, ASVariable>>,
+    /// Variables declared with `!local` outside of functions, which are deleted when another
+    /// script is loaded
+    pub script_variables: HashMap<String, ASVariable>,
+    /// Values declared with `!const`, which can't be changed
+    pub constants: HashMap<String, ASVariable>,
+    /// Variables and flags that are kept between playthroughs (`persist.name` or `??name`),
+    /// without the `persist.` prefix
+    pub persistent: HashMap<String, ASVariable>,
+    /// Achievements declared by the game
+    pub achievements: Vec<Achievement>,
+    /// Value given to `!return`, until the function it's in stops running
+    pub(crate) returning: Option<ASVariable>,
+    /// Flags and variables kept from the last completed playthrough, for New Game+
+    pub(crate) carried_over: Option<(HashMap<String, ASVariable>, HashMap<String, ASVariable>)>,
+    /// Time played before `playing_since`, in this playthrough
+    pub(crate) playtime: Duration,
+    pub(crate) playing_since: Instant,
+    /// Choices made since the last autosave
+    pub(crate) choices_since_autosave: u32,
+    /// State of the game before each of the last choices, oldest first
+    pub(crate) history: VecDeque<Save>,
+    /// Global variables defined by modules, named `module.global`
+    pub mod_globals: HashMap<String, ASVariable>,
+    pub(crate) mod_global_types: HashMap<String, ASType>,
+    pub limits: Limits,
+    /// Lines run since the last player interaction
+    pub steps: u64,
+    pub(crate) call_depth: usize,
+    pub(crate) nesting: usize,
+    pub(crate) listeners: Vec<EventListener>,
+    pub(crate) module_hooks: Vec<(String, ModuleHooks)>,
 }
 
 impl GameInfo {
-    pub fn create(root_dir: PathBuf, io: AdventureIO, local: bool, debug: bool) -> GameInfo {
+    pub fn create(source: GameSource, io: AdventureIO, local: bool, debug: bool) -> GameInfo {
         GameInfo {
-            io: io,
-            root_dir: root_dir,
+            io,
+            source,
             script_name: "start".to_string(),
             script: Vec::<String>::new(),
             pointer: 0,
@@ -41,9 +95,31 @@ impl GameInfo {
             local,
             debug,
             allow_save: true,
+            allow_undo: true,
             screentext: String::new(),
             objects: vec![],
+            structs: vec![],
+            commands: Rc::new(CmdSet::new()),
+            functions: Rc::new(CmdSet::new()),
+            locals: vec![],
+            script_variables: HashMap::new(),
+            constants: HashMap::new(),
+            persistent: HashMap::new(),
+            achievements: vec![],
+            returning: None,
+            carried_over: None,
+            playtime: Duration::ZERO,
+            playing_since: Instant::now(),
+            choices_since_autosave: 0,
+            history: VecDeque::new(),
             mod_globals: HashMap::new(),
+            mod_global_types: HashMap::new(),
+            limits: Limits::default(),
+            steps: 0,
+            call_depth: 0,
+            nesting: 0,
+            listeners: vec![],
+            module_hooks: vec![],
         }
     }
 
@@ -60,8 +136,8 @@ impl GameInfo {
     pub fn pointer(&self) -> i64 {
         self.pointer + 1
     }
-    pub fn root_dir(&self) -> &PathBuf {
-        &self.root_dir
+    pub fn source(&self) -> &GameSource {
+        &self.source
     }
     pub fn get_line(&self) -> anyhow::Result<&str> {
         //obtains the current line of the script
@@ -77,6 +153,34 @@ impl GameInfo {
             None => None,
         }
     }
+    /// obtains the name of the last label before the current line, if there is one
+    pub fn current_label(&self) -> Option<&str> {
+        self.current_label_line().map(|c| c.0)
+    }
+    /// obtains the name and line of the last label before the current line, if there is one
+    pub fn current_label_line(&self) -> Option<(&str, i64)> {
+        let end = (self.pointer.max(0) as usize + 1).min(self.script.len());
+        for (c, line) in self.script[..end].iter().enumerate().rev() {
+            let line = line.trim();
+            if line.starts_with('{') && line.ends_with('}') {
+                return Some((&line[1..line.len() - 1], c as i64));
+            }
+        }
+        None
+    }
+    /// obtains the line a label is in, if it's in the script exactly once
+    pub fn label_line(&self, name: &str) -> Option<i64> {
+        let label = format!("{{{}}}", name);
+        let mut instances = self
+            .script
+            .iter()
+            .enumerate()
+            .filter(|c| c.1.trim() == label);
+        match (instances.next(), instances.next()) {
+            (Some((c, _)), None) => Some(c as i64),
+            _ => None,
+        }
+    }
 
     // IO stuff
     pub fn show(&mut self, text: &str) -> anyhow::Result<()> {
@@ -84,7 +188,8 @@ impl GameInfo {
         self.screentext += &format!("{}\n", text);
         Ok(())
     }
-    pub fn wait(&self) -> anyhow::Result<()> {
+    pub fn wait(&mut self) -> anyhow::Result<()> {
+        self.steps = 0;
         self.io.wait()
     }
     pub fn error(&self, text: String) {
@@ -93,7 +198,12 @@ impl GameInfo {
     pub fn warn(&self, text: String) {
         self.io.warn(text)
     }
-    pub fn load_file(&self, filename: &str, mode: &str, ftype: FileType) -> anyhow::Result<File> {
+    pub fn load_file(
+        &self,
+        filename: &str,
+        mode: &str,
+        ftype: FileType,
+    ) -> anyhow::Result<Box<dyn ASFile>> {
         self.io.load_file(self, filename, mode, ftype)
     }
     //TODO: complete
@@ -109,18 +219,17 @@ impl GameInfo {
             ))?,
         };
 
-        let mut c = 0; //loop counter
         let mut instances = Vec::<i64>::new(); //lines where there's been a match
-        for line in &self.script {
+        for (c, line) in self.script.iter().enumerate() {
             if line.trim() == format!("{{{}}}", lname) {
-                instances.push(c);
+                instances.push(c as i64);
             }
-            c += 1;
         }
         match instances.len() {
             0 => Err(ASSyntaxError::NonExistentLabel(lname.to_string()))?,
             1 => {
-                self.pointer = *instances.get(0).unwrap();
+                self.pointer = *instances.first().unwrap();
+                self.emit(ASEvent::LabelEntered(lname.to_string()));
                 Ok(())
             }
             _ => Err(ASSyntaxError::RepeatedLabel(lname.to_string(), instances))?,
@@ -138,13 +247,46 @@ impl GameInfo {
     pub fn get_var(&mut self, var: &ASVariable) -> anyhow::Result<&ASVariable> {
         Ok(match var {
             ASVariable::VarRef { name, flag } => {
-                if *flag {
-                    if let None = self.flags.get(name) {
+                if let Some(name) = persistent_name(name) {
+                    if *flag && !self.persistent.contains_key(name) {
+                        self.persistent
+                            .insert(name.to_string(), ASVariable::Bool(false));
+                    }
+                    match self.persistent.get(name) {
+                        Some(c) => c,
+                        None => Err(ASVarError::VarNotFound(format!("persist.{}", name)))?,
+                    }
+                } else if *flag {
+                    if !self.flags.contains_key(name) {
                         self.flags.insert(name.to_string(), ASVariable::Bool(false));
                     }
                     self.flags.get(name).unwrap()
+                } else if self.is_local(name) {
+                    self.locals.last().unwrap().get(name).unwrap()
+                } else if self.constants.contains_key(name) {
+                    self.constants.get(name).unwrap()
+                } else if self.script_variables.contains_key(name) {
+                    self.script_variables.get(name).unwrap()
+                } else if self.mod_global_types.contains_key(name) {
+                    self.mod_globals.get(name).unwrap()
+                } else if let Some((base, field)) = name.rsplit_once('.') {
+                    match self.get_var(&ASVariable::VarRef {
+                        name: base.to_string(),
+                        flag: false,
+                    })? {
+                        ASVariable::Object { spec, fields } => match fields.get(field) {
+                            Some(c) => c,
+                            None => Err(ASVarError::UnknownField {
+                                spec: spec.to_string(),
+                                field: field.to_string(),
+                            })?,
+                        },
+                        c => Err(ASVarError::NotAnObject {
+                            name: base.to_string(),
+                            type_: c.get_type(),
+                        })?,
+                    }
                 } else {
-                    //TODO: modules / module globals
                     match self.variables.get(name) {
                         Some(c) => c,
                         None => Err(ASVarError::VarNotFound(name.to_string()))?,
@@ -160,11 +302,45 @@ impl GameInfo {
     pub fn get_var_mut(&mut self, var: &ASVariable) -> anyhow::Result<&mut ASVariable> {
         Ok(match var {
             ASVariable::VarRef { name, flag } => {
-                if *flag {
-                    if let None = self.flags.get(name) {
+                if let Some(name) = persistent_name(name) {
+                    if *flag && !self.persistent.contains_key(name) {
+                        self.persistent
+                            .insert(name.to_string(), ASVariable::Bool(false));
+                    }
+                    match self.persistent.get_mut(name) {
+                        Some(c) => c,
+                        None => Err(ASVarError::VarNotFound(format!("persist.{}", name)))?,
+                    }
+                } else if *flag {
+                    if !self.flags.contains_key(name) {
                         self.flags.insert(name.to_string(), ASVariable::Bool(false));
                     }
                     self.flags.get_mut(name).unwrap()
+                } else if self.is_local(name) {
+                    self.locals.last_mut().unwrap().get_mut(name).unwrap()
+                } else if self.constants.contains_key(name) {
+                    Err(ASVarError::ConstAssign(name.to_string()))?
+                } else if self.script_variables.contains_key(name) {
+                    self.script_variables.get_mut(name).unwrap()
+                } else if self.mod_global_types.contains_key(name) {
+                    self.mod_globals.get_mut(name).unwrap()
+                } else if let Some((base, field)) = name.rsplit_once('.') {
+                    match self.get_var_mut(&ASVariable::VarRef {
+                        name: base.to_string(),
+                        flag: false,
+                    })? {
+                        ASVariable::Object { spec, fields } => match fields.get_mut(field) {
+                            Some(c) => c,
+                            None => Err(ASVarError::UnknownField {
+                                spec: spec.to_string(),
+                                field: field.to_string(),
+                            })?,
+                        },
+                        c => Err(ASVarError::NotAnObject {
+                            name: base.to_string(),
+                            type_: c.get_type(),
+                        })?,
+                    }
                 } else {
                     match self.variables.get_mut(name) {
                         Some(c) => c,
@@ -180,15 +356,61 @@ impl GameInfo {
 
     pub fn set_var(&mut self, var: &ASVariable, value: ASVariable) -> anyhow::Result<()> {
         if let ASVariable::VarRef { name, flag } = var {
-            if *flag {
-                if let ASVariable::Bool(_) = value {
-                    self.flags.insert(name.to_string(), value);
-                } else {
-                    Err(ASVarError::FlagNotBool(name.to_string()))?;
+            if *flag && value.get_type() != ASType::Bool {
+                Err(ASVarError::FlagNotBool(name.to_string()))?;
+            }
+            if let Some(pname) = persistent_name(name) {
+                self.persistent.insert(pname.to_string(), value.clone());
+                persistent::save(self)?;
+            } else if *flag {
+                self.flags.insert(name.to_string(), value.clone());
+            } else if self.is_local(name) {
+                // parameters aren't part of the game's state, so no event is emitted
+                self.locals
+                    .last_mut()
+                    .unwrap()
+                    .insert(name.to_string(), value);
+                return Ok(());
+            } else if self.constants.contains_key(name) {
+                Err(ASVarError::ConstAssign(name.to_string()))?
+            } else if self.script_variables.contains_key(name) {
+                self.script_variables
+                    .insert(name.to_string(), value.clone());
+            } else if let Some(global_type) = self.mod_global_types.get(name) {
+                if *global_type != ASType::Any && *global_type != value.get_type() {
+                    Err(ASVarError::GlobalWrongType {
+                        name: name.to_string(),
+                        expected: global_type.clone(),
+                        given: value.get_type(),
+                    })?
+                }
+                self.mod_globals.insert(name.to_string(), value.clone());
+            } else if let Some((base, field)) = name.rsplit_once('.') {
+                let spec = self
+                    .get_var(&ASVariable::VarRef {
+                        name: base.to_string(),
+                        flag: false,
+                    })?
+                    .get_type();
+                match spec {
+                    ASType::Object(spec) => self.check_field(&spec, field, &value)?,
+                    type_ => Err(ASVarError::NotAnObject {
+                        name: base.to_string(),
+                        type_,
+                    })?,
+                }
+                *self.get_var_mut(var)? = value.clone();
+                if name.starts_with("persist.") {
+                    persistent::save(self)?;
                 }
             } else {
-                self.variables.insert(name.to_string(), value);
+                self.variables.insert(name.to_string(), value.clone());
             }
+            self.emit(ASEvent::VarChanged {
+                name: name.to_string(),
+                flag: *flag,
+                value,
+            });
         } else {
             Err(ASOtherError::DevErr(
                 "Tried to set the variable value of a non-VarRef value".to_string(),
@@ -197,15 +419,132 @@ impl GameInfo {
         Ok(())
     }
 
+    /// Gets the value of a variable by name, converted to a Rust type
+    pub fn get_var_value<T>(&self, name: &str) -> anyhow::Result<T>
+    where
+        T: TryFrom<ASVariable>,
+        anyhow::Error: From<T::Error>,
+    {
+        let var = if let Some(c) = self.constants.get(name) {
+            Some(c)
+        } else if let Some(c) = self.script_variables.get(name) {
+            Some(c)
+        } else if self.mod_global_types.contains_key(name) {
+            self.mod_globals.get(name)
+        } else {
+            self.variables.get(name)
+        };
+        match var {
+            Some(c) => Ok(T::try_from(c.clone())?),
+            None => Err(ASVarError::VarNotFound(name.to_string()))?,
+        }
+    }
+
+    /// Sets the value of a variable by name, from a Rust type
+    pub fn set_var_value(
+        &mut self,
+        name: &str,
+        value: impl Into<ASVariable>,
+    ) -> anyhow::Result<()> {
+        self.set_var(
+            &ASVariable::VarRef {
+                name: name.to_string(),
+                flag: false,
+            },
+            value.into(),
+        )
+    }
+
+    /// Gets the value of a persistent variable or flag by name (without the `persist.` prefix)
+    pub fn get_persistent(&self, name: &str) -> Option<&ASVariable> {
+        self.persistent.get(name)
+    }
+
+    /// Sets the value of a persistent variable or flag by name (without the `persist.` prefix),
+    /// and stores it right away
+    pub fn set_persistent(
+        &mut self,
+        name: &str,
+        value: impl Into<ASVariable>,
+    ) -> anyhow::Result<()> {
+        self.set_var(
+            &ASVariable::VarRef {
+                name: format!("persist.{}", name),
+                flag: false,
+            },
+            value.into(),
+        )
+    }
+
+    /// Gets the value of a flag by name. Flags that haven't been set are `false`.
+    pub fn get_flag(&self, name: &str) -> bool {
+        matches!(self.flags.get(name), Some(ASVariable::Bool(true)))
+    }
+
+    /// Sets the value of a flag by name
+    pub fn set_flag(&mut self, name: &str, value: bool) {
+        self.flags.insert(name.to_string(), ASVariable::Bool(value));
+        self.emit(ASEvent::VarChanged {
+            name: name.to_string(),
+            flag: true,
+            value: ASVariable::Bool(value),
+        });
+    }
+
+    /// Whether a variable name refers to a parameter of the function that's running
+    fn is_local(&self, name: &str) -> bool {
+        match self.locals.last() {
+            Some(c) => c.contains_key(name),
+            None => false,
+        }
+    }
+
+    /// Declares a local variable: inside a function, it only exists until the function stops
+    /// running, and outside of one, until another script is loaded
+    pub fn set_local(&mut self, name: &str, value: ASVariable) -> anyhow::Result<()> {
+        if self.constants.contains_key(name) {
+            Err(ASVarError::LocalIsConst(name.to_string()))?
+        }
+        match self.locals.last_mut() {
+            Some(c) => {
+                c.insert(name.to_string(), value);
+            }
+            None => {
+                self.script_variables.insert(name.to_string(), value);
+            }
+        }
+        Ok(())
+    }
+
     pub fn del_var(&mut self, var: &ASVariable) -> anyhow::Result<()> {
         if let ASVariable::VarRef { name, flag } = var {
-            if *flag {
+            if let Some(pname) = persistent_name(name) {
+                if self.persistent.remove(pname).is_none() && !*flag {
+                    Err(ASVarError::VarNotFound(name.to_string()))?
+                }
+                persistent::save(self)?;
+            } else if *flag {
                 self.flags.remove(&name.to_string());
+            } else if self.is_local(name) {
+                self.locals.last_mut().unwrap().remove(name);
+                return Ok(());
+            } else if self.constants.contains_key(name) {
+                Err(ASVarError::ConstAssign(name.to_string()))?
+            } else if self.script_variables.contains_key(name) {
+                self.script_variables.remove(name);
+            } else if self.mod_global_types.contains_key(name) {
+                Err(ASVarError::DeleteGlobal(name.to_string()))?
+            } else if name.contains('.') {
+                Err(ASVarError::DeleteField(name.to_string()))?
             } else {
-                if let None = self.variables.remove(name) {
+                if self.variables.remove(name).is_none() {
                     Err(ASVarError::VarNotFound(name.to_string()))?
                 }
             }
+            self.emit(ASEvent::VarDeleted {
+                name: name.to_string(),
+                flag: *flag,
+            });
         } else {
             Err(ASOtherError::DevErr(
                 "Tried to delete the variable value of a non-VarRef value".to_string(),
@@ -214,21 +553,71 @@ impl GameInfo {
         Ok(())
     }
 
+    /// Whether the game can be saved right now: saving isn't disabled with `!save false`, and
+    /// no function defined with `!def` is running, since saves don't store function calls
+    pub fn can_save(&self) -> bool {
+        self.allow_save && self.locals.is_empty()
+    }
+
     //TODO: customization of choice text formatting
     pub fn query(&mut self, text: &str, choices: Vec<&str>) -> anyhow::Result<u8> {
+        let autosave = self.config.as_ref().map_or(0, |c| c.autosave);
+        if autosave > 0 && self.choices_since_autosave >= autosave {
+            // saved before the choice is shown, so restoring shows it again
+            save::autosave(self)?;
+            self.choices_since_autosave = 0;
+        }
+        let undo = self.config.as_ref().map_or(0, |c| c.undo);
+        // like saves, snapshots can't store the functions that are running
+        let state = if undo > 0 && self.allow_undo && self.locals.is_empty() {
+            Some(save::snapshot(self)?)
+        } else {
+            None
+        };
+        // engine menus go through ask directly, so hosts only hear about the game's choices
+        let choices: Vec<String> = choices.iter().map(|c| c.to_string()).collect();
+        self.emit(ASEvent::ChoicePresented {
+            text: text.to_string(),
+            choices: choices.clone(),
+        });
+        let choice = self.ask(text, choices.iter().map(|c| c.as_str()).collect())?;
+        if choice != 0 {
+            if let Some(state) = state {
+                self.record_choice(state);
+            }
+            self.emit(ASEvent::ChoiceMade {
+                number: choice,
+                text: choices[choice as usize - 1].clone(),
+            });
+        }
+        Ok(choice)
+    }
+
+    /// Asks the player to pick one of the choices in a menu that isn't part of the game, like
+    /// the title screen, so it can't be saved or undone
+    pub(crate) fn menu(&mut self, text: &str, choices: Vec<&str>) -> anyhow::Result<u8> {
+        let (allow_save, allow_undo) = (self.allow_save, self.allow_undo);
+        self.allow_save = false;
+        self.allow_undo = false;
+        let choice = self.ask(text, choices);
+        self.allow_save = allow_save;
+        self.allow_undo = allow_undo;
+        choice
+    }
+
+    fn ask(&mut self, text: &str, choices: Vec<&str>) -> anyhow::Result<u8> {
         if !text.is_empty() {
-            self.io.show(&text)?;
+            self.io.show(text)?;
         }
-        let mut c = 1;
-        for ch in &choices {
-            self.io.show(&format!("{}. {}", c, ch))?;
-            c += 1;
+        for (c, ch) in choices.iter().enumerate() {
+            self.io.show(&format!("{}. {}", c + 1, ch))?;
         }
+        self.steps = 0;
         loop {
             let result = self.io.input()?;
             match result.trim() {
                 "s" => {
-                    if self.allow_save {
+                    if self.can_save() {
                         save::save(self)?;
                     }
                     return Ok(0);
@@ -239,6 +628,22 @@ impl GameInfo {
                     }
                     return Ok(0);
                 }
+                "qs" => {
+                    save::quicksave(self)?;
+                    return Ok(0);
+                }
+                "ql" => {
+                    if self.allow_save {
+                        save::quickload(self)?;
+                    }
+                    return Ok(0);
+                }
+                "u" => {
+                    if self.allow_undo && !self.undo(1)? {
+                        self.io.show("Nothing to undo")?;
+                    }
+                    return Ok(0);
+                }
                 "q" => {
                     self.quit();
                     return Ok(0);
@@ -251,6 +656,9 @@ impl GameInfo {
             };
             if (num_result as usize) <= choices.len() {
                 self.screentext = String::new();
+                if num_result != 0 && self.allow_save {
+                    self.choices_since_autosave += 1;
+                }
                 return Ok(num_result);
             }
         }
@@ -264,17 +672,84 @@ impl GameInfo {
             }
             None => &self.script_name,
         };
+        self.script = self.read_script(filename)?;
+        self.pointer = 0;
+        self.script_variables = HashMap::new();
+        parsing::load_declarations(self)?;
+        self.emit(ASEvent::ScriptLoaded(self.script_name.to_string()));
+        Ok(())
+    }
+
+    fn read_script(&self, filename: &str) -> anyhow::Result<Vec<String>> {
         let mut file = String::from("");
-        self.script = vec![];
         self.io
             .load_file(self, &format!("{}.as2", filename), "r", FileType::Script)?
             .read_to_string(&mut file)?;
-        let lines = file.split("\n");
-        for line in lines {
-            self.script.push(line.to_string());
+        Ok(file.split('\n').map(|c| c.to_string()).collect())
+    }
+
+    /// Runs `f` with another script loaded, in a scope of its own: what the script declares
+    /// (constants, functions and structs) is dropped afterwards, and the current script and
+    /// position are restored. No `ScriptLoaded` events are emitted.
+    pub(crate) fn with_script<T>(
+        &mut self,
+        filename: &str,
+        f: impl FnOnce(&mut Self) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        let script = self.read_script(filename)?;
+        let script = std::mem::replace(&mut self.script, script);
+        let script_name = std::mem::replace(&mut self.script_name, filename.to_string());
+        let pointer = std::mem::replace(&mut self.pointer, 0);
+        let script_variables = std::mem::take(&mut self.script_variables);
+        let functions = self.functions.clone();
+        let constants = self.constants.clone();
+        let objects = self.objects.clone();
+        let structs = self.structs.clone();
+
+        let result = parsing::load_declarations(self).and_then(|_| f(self));
+
+        self.script = script;
+        self.script_name = script_name;
+        self.pointer = pointer;
+        self.script_variables = script_variables;
+        self.functions = functions;
+        self.constants = constants;
+        self.objects = objects;
+        self.structs = structs;
+        result
+    }
+
+    /// Starts a new playthrough from the start script, clearing everything from the previous
+    /// one except for persistent variables and constants
+    pub fn new_game(&mut self) -> anyhow::Result<()> {
+        self.flags = HashMap::new();
+        self.variables = HashMap::new();
+        self.locals = vec![];
+        self.returning = None;
+        self.screentext = String::new();
+        self.quitting = false;
+        self.allow_save = true;
+        self.set_playtime(Duration::ZERO);
+        self.choices_since_autosave = 0;
+        self.allow_undo = true;
+        self.history.clear();
+        for (name, type_) in self.mod_global_types.clone() {
+            let value = self.default_for_type(&type_);
+            self.mod_globals.insert(name, value);
         }
-        self.pointer = 0;
-        Ok(())
+        self.load_script(Some("start"))?;
+        self.module_game_start()
+    }
+
+    /// Time played in the current playthrough, including the time played before restoring
+    /// a save
+    pub fn playtime(&self) -> Duration {
+        self.playtime + self.playing_since.elapsed()
+    }
+
+    pub(crate) fn set_playtime(&mut self, playtime: Duration) {
+        self.playtime = playtime;
+        self.playing_since = Instant::now();
     }
 
     pub(crate) fn show_screentext(&self) -> anyhow::Result<()> {
@@ -285,13 +760,18 @@ impl GameInfo {
         &mut self,
         objects: Vec<ObjSpec>,
         globals: HashMap<String, ASType>,
+        hooks: ModuleHooks,
         name: &str,
     ) {
+        self.module_hooks.push((name.to_string(), hooks));
         for object in objects {
             self.objects.push(object.adapt_for_module(name));
         }
         for (gname, global) in globals {
-            self.mod_globals.insert(format!("{}.{}", name, gname), global.default_for_type());
+            let gname = format!("{}.{}", name, gname);
+            self.mod_globals
+                .insert(gname.clone(), self.default_for_type(&global));
+            self.mod_global_types.insert(gname, global);
         }
     }
 
@@ -301,6 +781,82 @@ impl GameInfo {
                 return Some(object.clone());
             }
         }
-        return None;
+        None
+    }
+
+    /// Same as [ASType::default_for_type], but objects get their fields initialized according to
+    /// their [ObjSpec]
+    pub fn default_for_type(&self, type_: &ASType) -> ASVariable {
+        match type_ {
+            ASType::Object(spec) => match self.get_object(spec) {
+                Some(object) => ASVariable::Object {
+                    spec: spec.to_string(),
+                    fields: object
+                        .fields
+                        .iter()
+                        .map(|(name, type_)| {
+                            (
+                                name.to_string(),
+                                match object.defaults.get(name) {
+                                    Some(c) => c.clone(),
+                                    None => self.default_for_type(type_),
+                                },
+                            )
+                        })
+                        .collect(),
+                },
+                None => type_.default_for_type(),
+            },
+            c => c.default_for_type(),
+        }
     }
+
+    /// Creates an object of the given type, with the given values for its fields. Fields that
+    /// aren't given a value get the default one for their type.
+    pub fn new_object(
+        &self,
+        spec: &str,
+        values: HashMap<String, ASVariable>,
+    ) -> anyhow::Result<ASVariable> {
+        if self.get_object(spec).is_none() {
+            Err(ASVarError::UnknownObject(spec.to_string()))?
+        }
+        let mut object = self.default_for_type(&ASType::Object(spec.to_string()));
+        for (field, value) in values {
+            self.check_field(spec, &field, &value)?;
+            if let ASVariable::Object { fields, .. } = &mut object {
+                fields.insert(field, value);
+            }
+        }
+        Ok(object)
+    }
+
+    /// Checks that an object type has the given field, and that the value is of the right type for it
+    fn check_field(&self, spec: &str, field: &str, value: &ASVariable) -> anyhow::Result<()> {
+        let field_type = match self.get_object(spec) {
+            Some(c) => match c.fields.get(field) {
+                Some(c) => c.clone(),
+                None => Err(ASVarError::UnknownField {
+                    spec: spec.to_string(),
+                    field: field.to_string(),
+                })?,
+            },
+            None => Err(ASVarError::UnknownObject(spec.to_string()))?,
+        };
+        if field_type != ASType::Any && field_type != value.get_type() {
+            Err(ASVarError::FieldWrongType {
+                spec: spec.to_string(),
+                field: field.to_string(),
+                expected: field_type,
+                given: value.get_type(),
+            })?
+        }
+        Ok(())
+    }
+}
+
+/// Gets the name of a persistent variable or flag without the `persist.` prefix, unless it's a
+/// field of one
+fn persistent_name(name: &str) -> Option<&str> {
+    name.strip_prefix("persist.").filter(|c| !c.contains('.'))
 }
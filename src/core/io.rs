@@ -1,21 +1,33 @@
-use crate::core::{
-    error::{ASFileError, ASOtherError, FileErrors},
-    GameInfo,
+use crate::{
+    core::{
+        error::{ASFileError, ASOtherError, FileErrors},
+        source::{confine, open_path, sandbox_path},
+        GameInfo,
+    },
+    formats::achievements::Achievement,
 };
 use anyhow;
 use std::{
-    fs::File,
     io::{stdin, stdout, Read, Write},
     path::PathBuf,
 };
 
+/// A file opened through [AdventureIO], which might be on disk, inside an archive or in memory
+pub trait ASFile: Read + Write {}
+
+impl<T: Read + Write> ASFile for T {}
+
+/// Signature of the function used by [AdventureIO] to open game and save files
+pub type LoadFileFn = fn(&GameInfo, &str, &str, FileType) -> anyhow::Result<Box<dyn ASFile>>;
+
 fn show_(text: &str) -> anyhow::Result<()> {
     println!("{}", text);
     Ok(())
 }
 
 fn wait_() -> anyhow::Result<()> {
-    stdin().read(&mut [0])?;
+    // a closed stdin just means there's nothing to wait for
+    let _ = stdin().read(&mut [0])?;
     Ok(())
 }
 
@@ -23,7 +35,10 @@ fn input_() -> anyhow::Result<String> {
     print!("> ");
     stdout().flush()?;
     let mut result = String::new();
-    stdin().read_line(&mut result)?;
+    if stdin().read_line(&mut result)? == 0 {
+        // without this, choices would keep asking forever once stdin is closed
+        Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof))?
+    }
     Ok(result)
 }
 
@@ -36,7 +51,7 @@ pub enum FileType {
 
 fn pc_save_location(info: &GameInfo) -> anyhow::Result<std::path::PathBuf> {
     //TODO: other platforms?
-    return match dirs::data_local_dir() {
+    match dirs::data_local_dir() {
         Some(c) => {
             let mut c = c;
             c.extend(&PathBuf::from("AdventureScript"));
@@ -47,7 +62,32 @@ fn pc_save_location(info: &GameInfo) -> anyhow::Result<std::path::PathBuf> {
             Ok(c)
         }
         None => Err(ASOtherError::UnsupportedPlatform)?,
+    }
+}
+
+fn save_path(info: &GameInfo, filename: &str) -> anyhow::Result<PathBuf> {
+    // saves never go inside the game source, since it might not be writable
+    let dir = if info.local {
+        info.source.local_save_dir()
+    } else {
+        pc_save_location(info)?
     };
+    let fname = dir.join(sandbox_path(&PathBuf::from(filename))?);
+    confine(&dir, &fname, "")?;
+    Ok(fname)
+}
+
+fn delete_save_(info: &GameInfo, filename: &str) -> anyhow::Result<()> {
+    let fname = save_path(info, filename)?;
+    match std::fs::remove_file(&fname) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(ASFileError::from(
+            &fname.to_string_lossy(),
+            "w",
+            FileErrors::NotFound,
+        ))?,
+        Err(e) => Err(e)?,
+    }
 }
 
 fn load_file_(
@@ -55,62 +95,20 @@ fn load_file_(
     filename: &str,
     mode: &str,
     ftype: FileType,
-) -> anyhow::Result<File> {
+) -> anyhow::Result<Box<dyn ASFile>> {
     let folder = match ftype {
         FileType::Script => PathBuf::from("script"),
         FileType::CustomDir(c) => PathBuf::from(c),
-        FileType::Save => {
-            if info.local {
-                PathBuf::from("save")
-            } else {
-                pc_save_location(info)?
-            }
-        }
+        FileType::Save => return open_path(&save_path(info, filename)?, mode),
         FileType::Other => PathBuf::new(),
     };
 
-    let mut fname = info.root_dir().clone();
-    fname.push(&folder);
-
-    if mode == "w" && !fname.is_dir() {
-        std::fs::create_dir_all(&fname)?
-    }
-
-    fname.push(PathBuf::from(filename));
-
-    //this manages std::io errors
-    let return_errors = |i: std::io::Result<File>| -> anyhow::Result<File> {
-        match i {
-            Ok(c) => Ok(c),
-            Err(e) => {
-                use std::io::ErrorKind as EK;
-                match e.kind() {
-                    EK::NotFound => Err(ASFileError::from(
-                        &fname.to_string_lossy(),
-                        mode,
-                        FileErrors::NotFound,
-                    ))?,
-                    EK::PermissionDenied => Err(ASFileError::from(
-                        &fname.to_string_lossy(),
-                        mode,
-                        FileErrors::MissingPermissions,
-                    ))?,
-
-                    _ => Err(e)?,
-                }
-            }
-        }
-    };
+    info.source.open(&folder.join(filename), mode)
+}
 
-    Ok(match mode {
-        "r" => return_errors(File::open(&fname))?,
-        "w" => return_errors(File::create(&fname))?,
-        _ => Err(ASFileError::from(
-            &fname.to_string_lossy(),
-            mode,
-            FileErrors::InvalidMode(mode.to_string()),
-        ))?,
-    })
+fn unlock_(achievement: &Achievement) -> anyhow::Result<()> {
+    println!("Achievement unlocked: {}", achievement.name);
+    Ok(())
 }
 
 fn error_(text: String) {
@@ -125,9 +123,11 @@ pub struct AdventureIO {
     show: fn(&str) -> anyhow::Result<()>,
     wait: fn() -> anyhow::Result<()>,
     input: fn() -> anyhow::Result<String>,
-    load_file: fn(&GameInfo, &str, &str, FileType) -> anyhow::Result<File>,
+    load_file: LoadFileFn,
     error: fn(String),
     warn: fn(String),
+    unlock: fn(&Achievement) -> anyhow::Result<()>,
+    delete_save: fn(&GameInfo, &str) -> anyhow::Result<()>,
 }
 
 impl AdventureIO {
@@ -146,7 +146,7 @@ impl AdventureIO {
         filename: &str,
         mode: &str,
         ftype: FileType,
-    ) -> anyhow::Result<File> {
+    ) -> anyhow::Result<Box<dyn ASFile>> {
         (self.load_file)(info, filename, mode, ftype)
     }
     pub fn error(&self, text: String) {
@@ -155,12 +155,18 @@ impl AdventureIO {
     pub fn warn(&self, text: String) {
         (self.warn)(text)
     }
+    pub fn unlock(&self, achievement: &Achievement) -> anyhow::Result<()> {
+        (self.unlock)(achievement)
+    }
+    pub fn delete_save(&self, info: &GameInfo, filename: &str) -> anyhow::Result<()> {
+        (self.delete_save)(info, filename)
+    }
 
     pub fn default_with(
         show: Option<fn(&str) -> anyhow::Result<()>>,
         wait: Option<fn() -> anyhow::Result<()>>,
         input: Option<fn() -> anyhow::Result<String>>,
-        load_file: Option<fn(&GameInfo, &str, &str, FileType) -> anyhow::Result<File>>,
+        load_file: Option<LoadFileFn>,
         error: Option<fn(String)>,
         warn: Option<fn(String)>,
     ) -> Self {
@@ -171,8 +177,26 @@ impl AdventureIO {
             load_file: load_file.unwrap_or(load_file_),
             error: error.unwrap_or(error_),
             warn: warn.unwrap_or(warn_),
+            unlock: unlock_,
+            delete_save: delete_save_,
         }
     }
+
+    /// Sets the function used to tell the player they unlocked an achievement
+    pub fn with_unlock(mut self, unlock: fn(&Achievement) -> anyhow::Result<()>) -> Self {
+        self.unlock = unlock;
+        self
+    }
+
+    /// Sets the function used to delete save files, for IO systems that store saves somewhere
+    /// other than the save folder
+    pub fn with_delete_save(
+        mut self,
+        delete_save: fn(&GameInfo, &str) -> anyhow::Result<()>,
+    ) -> Self {
+        self.delete_save = delete_save;
+        self
+    }
 }
 
 impl Default for AdventureIO {
@@ -184,6 +208,8 @@ impl Default for AdventureIO {
             load_file: load_file_,
             error: error_,
             warn: warn_,
+            unlock: unlock_,
+            delete_save: delete_save_,
         }
     }
 }
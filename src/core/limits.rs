@@ -0,0 +1,81 @@
+use crate::core::{error::ASLimitError, ASVariable, GameInfo};
+
+/// Limits on the resources a game can use, so buggy or malicious scripts end with an error
+/// instead of hanging or running out of memory.
+#[derive(Debug, Clone)]
+pub struct Limits {
+    /// Maximum amount of lines that can be run between two player interactions
+    /// (choices, `!wait`...)
+    pub max_steps: u64,
+    /// Maximum length of a `String`, or amount of items in a `List` or `Map`
+    pub max_size: usize,
+    /// Maximum nesting of method and function calls
+    pub max_call_depth: usize,
+    /// Maximum nesting of brackets inside an expression
+    pub max_nesting: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_steps: 100_000,
+            max_size: 1_000_000,
+            max_call_depth: 64,
+            max_nesting: 64,
+        }
+    }
+}
+
+impl Limits {
+    /// Returns an error if the value (or any value inside it) is bigger than `max_size`
+    pub fn check_size(&self, value: &ASVariable) -> anyhow::Result<()> {
+        let size = value.size();
+        if size > self.max_size {
+            Err(ASLimitError::ValueTooBig {
+                size,
+                max: self.max_size,
+            })?
+        }
+        Ok(())
+    }
+}
+
+impl GameInfo {
+    /// Counts a line being run. Errors if too many lines have been run since the last
+    /// player interaction.
+    pub fn count_step(&mut self) -> anyhow::Result<()> {
+        self.steps += 1;
+        if self.steps > self.limits.max_steps {
+            Err(ASLimitError::TooManySteps(self.limits.max_steps))?
+        }
+        Ok(())
+    }
+
+    /// Runs a function one call level deeper, erroring if `max_call_depth` is exceeded
+    pub fn nested_call<T>(
+        &mut self,
+        func: impl FnOnce(&mut Self) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        if self.call_depth >= self.limits.max_call_depth {
+            Err(ASLimitError::CallDepth(self.limits.max_call_depth))?
+        }
+        self.call_depth += 1;
+        let out = func(self);
+        self.call_depth -= 1;
+        out
+    }
+
+    /// Runs a function one bracket level deeper, erroring if `max_nesting` is exceeded
+    pub(crate) fn nested_expr<T>(
+        &mut self,
+        func: impl FnOnce(&mut Self) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        if self.nesting >= self.limits.max_nesting {
+            Err(ASLimitError::TooDeep(self.limits.max_nesting))?
+        }
+        self.nesting += 1;
+        let out = func(self);
+        self.nesting -= 1;
+        out
+    }
+}
@@ -7,26 +7,74 @@ use crate::{
 };
 use std::collections::HashMap;
 
+/// The function that runs a method, which gets the value it was called on and its arguments
+#[derive(Clone, Copy)]
+pub enum MethodFn {
+    /// A method that only reads the value it was called on
+    Ref(fn(&GameInfo, &ASVariable, Vec<ASVariable>) -> anyhow::Result<ASVariable>),
+    /// A method that can modify the value it was called on. It can only be called on variables,
+    /// which get the modified value once the method finishes.
+    Mut(fn(&GameInfo, &mut ASVariable, Vec<ASVariable>) -> anyhow::Result<ASVariable>),
+}
+
 #[derive(Clone)]
 pub struct Method {
     pub name: String,
-    func: fn(&GameInfo, &ASVariable, Vec<ASVariable>) -> anyhow::Result<ASVariable>,
+    func: MethodFn,
     argtypes: Vec<ASType>,
     required_args: usize,
     deprecated: bool,
 }
 
 impl Method {
+    pub fn new(
+        name: String,
+        func: fn(&GameInfo, &ASVariable, Vec<ASVariable>) -> anyhow::Result<ASVariable>,
+        argtypes: Vec<ASType>,
+        required_args: usize,
+        deprecated: bool,
+    ) -> Self {
+        Self {
+            name,
+            func: MethodFn::Ref(func),
+            argtypes,
+            required_args,
+            deprecated,
+        }
+    }
+    /// Same as [Method::new], but for methods that modify the value they're called on
+    pub fn new_mut(
+        name: String,
+        func: fn(&GameInfo, &mut ASVariable, Vec<ASVariable>) -> anyhow::Result<ASVariable>,
+        argtypes: Vec<ASType>,
+        required_args: usize,
+        deprecated: bool,
+    ) -> Self {
+        Self {
+            name,
+            func: MethodFn::Mut(func),
+            argtypes,
+            required_args,
+            deprecated,
+        }
+    }
+    /// Runs the method on `var`, which can be a value or a `VarRef` to the variable holding it
     pub fn run(
         self,
         info: &mut GameInfo,
         var: &ASVariable,
         mut args: Vec<ASVariable>,
     ) -> anyhow::Result<ASVariable> {
+        let mut value = if let ASVariable::VarRef { .. } = var {
+            info.get_var(var)?.clone()
+        } else {
+            var.clone()
+        };
+
         if args.len() > self.argtypes.len() {
             Err(ASMethodError {
                 method: String::from(&self.name),
-                type_name: var.get_type().to_string(),
+                type_name: value.get_type().to_string(),
                 details: MethodErrors::TooManyArguments {
                     given_args: args.len(),
                     max_args: self.argtypes.len(),
@@ -41,13 +89,13 @@ impl Method {
                 && self.argtypes[argnum] != arg_type
             {
                 if arg_type == ASType::VarRef {
-                    args.insert(argnum, info.get_var(arg)?.clone());
+                    args[argnum] = info.get_var(arg)?.clone();
                 } else if arg_type == ASType::None && self.argtypes[argnum] == ASType::Label {
-                    args.insert(argnum, ASVariable::Label(None));
+                    args[argnum] = ASVariable::Label(None);
                 } else {
                     Err(ASMethodError {
                         method: String::from(&self.name),
-                        type_name: var.get_type().to_string(),
+                        type_name: value.get_type().to_string(),
                         details: MethodErrors::ArgumentTypeError {
                             argument_num: argnum,
                             required_type: self.argtypes[argnum].clone(),
@@ -63,7 +111,7 @@ impl Method {
         if argnum < self.required_args {
             Err(ASMethodError {
                 method: String::from(&self.name),
-                type_name: var.get_type().to_string(),
+                type_name: value.get_type().to_string(),
                 details: MethodErrors::MissingRequiredArgument {
                     argument_num: argnum,
                     argument_type: self.argtypes.get(argnum).unwrap().clone(),
@@ -75,11 +123,26 @@ impl Method {
             info.warn(format!(
                 "Method '{}' for object type {} is deprecated",
                 self.name,
-                var.get_type()
+                value.get_type()
             ));
         }
 
-        (self.func)(info, var, args)
+        match self.func {
+            MethodFn::Ref(func) => info.nested_call(|info| func(info, &value, args)),
+            MethodFn::Mut(func) => {
+                if var.get_type() != ASType::VarRef {
+                    Err(ASMethodError {
+                        method: String::from(&self.name),
+                        type_name: value.get_type().to_string(),
+                        details: MethodErrors::NotAVariable,
+                    })?
+                }
+                let out = info.nested_call(|info| func(info, &mut value, args))?;
+                info.limits.check_size(&value)?;
+                info.set_var(var, value)?;
+                Ok(out)
+            }
+        }
     }
 }
 
@@ -89,6 +152,12 @@ pub struct TypeMethods {
     aliases: HashMap<String, String>,
 }
 
+impl Default for TypeMethods {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TypeMethods {
     pub fn new() -> Self {
         Self {
@@ -100,7 +169,7 @@ impl TypeMethods {
         Self::from(
             vec![Method {
                 name: "str".to_string(),
-                func: |info, var, _args| {
+                func: MethodFn::Ref(|info, var, _args| {
                     Ok(ASVariable::String(match var {
                         ASVariable::String(c) => format!("{:?}", c),
                         ASVariable::Object { spec, fields } => {
@@ -108,7 +177,7 @@ impl TypeMethods {
                         }
                         var => var.to_string(),
                     }))
-                },
+                }),
                 argtypes: vec![],
                 required_args: 0,
                 deprecated: false,
@@ -140,7 +209,6 @@ impl TypeMethods {
         var: &ASVariable,
         args: Vec<ASVariable>,
     ) -> anyhow::Result<ASVariable> {
-        //TODO: Object/Module fields???
         match self.get(name) {
             Some(c) => c.clone().run(info, var, args),
             None => Err(ASSyntaxError::UnknownMethod(
@@ -157,12 +225,11 @@ impl TypeMethods {
 
     pub fn get_for_type(info: &GameInfo, type_: &ASType) -> Self {
         let mut out = match type_ {
-            ASType::VarRef => todo!("Proper handling of VarRef methods"),
             ASType::List => Self::from(
                 vec![
                     Method {
                         name: "get".to_string(),
-                        func: |_info, var, args| -> anyhow::Result<ASVariable> {
+                        func: MethodFn::Ref(|_info, var, args| -> anyhow::Result<ASVariable> {
                             let pos = *unwrap_var!(args -> 0; Int)?;
                             if pos < 0 {
                                 Err(ASVarError::NegativeListIndex)?;
@@ -178,14 +245,14 @@ impl TypeMethods {
                             } else {
                                 panic!()
                             }
-                        },
+                        }),
                         argtypes: vec![ASType::Int],
                         required_args: 1,
                         deprecated: false,
                     },
                     Method {
                         name: "index_of".to_string(),
-                        func: |_info, var, args| -> anyhow::Result<ASVariable> {
+                        func: MethodFn::Ref(|_info, var, args| -> anyhow::Result<ASVariable> {
                             if let ASVariable::List(list) = var {
                                 let var = &args[0];
                                 Ok(match list.iter().position(|r| r == var) {
@@ -195,7 +262,21 @@ impl TypeMethods {
                             } else {
                                 panic!()
                             }
-                        },
+                        }),
+                        argtypes: vec![ASType::Any],
+                        required_args: 1,
+                        deprecated: false,
+                    },
+                    Method {
+                        name: "append".to_string(),
+                        func: MethodFn::Mut(|_info, var, args| -> anyhow::Result<ASVariable> {
+                            if let ASVariable::List(list) = var {
+                                list.push(args[0].clone());
+                                Ok(ASVariable::None)
+                            } else {
+                                panic!()
+                            }
+                        }),
                         argtypes: vec![ASType::Any],
                         required_args: 1,
                         deprecated: false,
@@ -204,29 +285,46 @@ impl TypeMethods {
                 HashMap::new(),
             ),
             ASType::Map => Self::from(
-                vec![Method {
-                    name: "get".to_string(),
-                    func: |_info, var, args| -> anyhow::Result<ASVariable> {
-                        let key = match args.get(0) {
-                            Some(c) => c.clone(),
-                            None => panic!(),
-                        }
-                        .clone()
-                        .as_key()?;
+                vec![
+                    Method {
+                        name: "get".to_string(),
+                        func: MethodFn::Ref(|_info, var, args| -> anyhow::Result<ASVariable> {
+                            let key = match args.first() {
+                                Some(c) => c.clone(),
+                                None => panic!(),
+                            }
+                            .clone()
+                            .as_key()?;
 
-                        if let ASVariable::Map(map) = var {
-                            match map.get(&key) {
-                                Some(c) => Ok(c.clone()),
-                                None => Err(ASVarError::WrongMapKey { key: key })?,
+                            if let ASVariable::Map(map) = var {
+                                match map.get(&key) {
+                                    Some(c) => Ok(c.clone()),
+                                    None => Err(ASVarError::WrongMapKey { key })?,
+                                }
+                            } else {
+                                panic!()
                             }
-                        } else {
-                            panic!()
-                        }
+                        }),
+                        argtypes: vec![ASType::Any],
+                        required_args: 1,
+                        deprecated: false,
+                    },
+                    Method {
+                        name: "insert".to_string(),
+                        func: MethodFn::Mut(|_info, var, args| -> anyhow::Result<ASVariable> {
+                            let key = args[0].clone().as_key()?;
+                            if let ASVariable::Map(map) = var {
+                                map.insert(key, args[1].clone());
+                                Ok(ASVariable::None)
+                            } else {
+                                panic!()
+                            }
+                        }),
+                        argtypes: vec![ASType::Any, ASType::Any],
+                        required_args: 2,
+                        deprecated: false,
                     },
-                    argtypes: vec![ASType::Any],
-                    required_args: 1,
-                    deprecated: false,
-                }],
+                ],
                 HashMap::new(),
             ),
             ASType::Object(spec) => info.get_object(spec).unwrap().methods,
@@ -1,14 +1,25 @@
 pub mod error;
 
 mod commands;
+mod events;
 mod info;
 mod io;
+mod limits;
 mod methods;
+mod source;
+mod title;
+mod undo;
 mod variables;
 
+#[cfg(test)]
+pub(crate) mod tests;
+
 // TODO: (more) public imports for stuff that might be used in the interface
-pub use commands::{main_commands, CmdSet, Command};
+pub use commands::{main_commands, CmdSet, Command, CommandFn};
+pub use events::{ASEvent, EventListener};
 pub use info::GameInfo;
-pub use io::{AdventureIO, FileType};
-pub use methods::{Method, TypeMethods};
+pub use io::{ASFile, AdventureIO, FileType, LoadFileFn};
+pub use limits::Limits;
+pub use methods::{Method, MethodFn, TypeMethods};
+pub use source::GameSource;
 pub use variables::{ASType, ASVariable, KeyVar};
@@ -0,0 +1,223 @@
+use crate::core::{
+    error::{ASFileError, FileErrors},
+    io::ASFile,
+};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{Cursor, Read},
+    path::{Component, Path, PathBuf},
+};
+
+/// Where the files for a game (config, scripts, custom module folders...) are read from.
+///
+/// Saves are never written to the game source itself: they go to the local `save` folder
+/// (see [GameSource::local_save_dir]) or to the user's data directory.
+pub enum GameSource {
+    /// A folder on the filesystem, with the usual `info.toml` + `script/` structure
+    Directory(PathBuf),
+    /// A zip archive with the same structure as a game folder
+    Archive(PathBuf),
+    /// A map of file paths (such as `"info.toml"` or `"script/start.as2"`) to their contents,
+    /// useful for tests or for embedding a game in a binary
+    Memory(HashMap<String, Vec<u8>>),
+}
+
+impl GameSource {
+    /// Creates a `Directory` source, or an `Archive` source if the path points to a file.
+    pub fn from_path(path: PathBuf) -> Self {
+        if path.is_file() {
+            Self::Archive(path)
+        } else {
+            Self::Directory(path)
+        }
+    }
+
+    /// Creates a `Memory` source from pairs of file paths and text contents.
+    pub fn from_memory<T: ToString>(files: HashMap<String, T>) -> Self {
+        Self::Memory(
+            files
+                .into_iter()
+                .map(|(name, contents)| (name, contents.to_string().into_bytes()))
+                .collect(),
+        )
+    }
+
+    /// Folder used for saves when the game is run as "portable"
+    pub fn local_save_dir(&self) -> PathBuf {
+        match self {
+            Self::Directory(c) => c.join("save"),
+            Self::Archive(c) => match c.parent() {
+                Some(c) => c.join("save"),
+                None => PathBuf::from("save"),
+            },
+            Self::Memory(_) => PathBuf::from("save"),
+        }
+    }
+
+    /// Opens a file from the game source. `path` is relative to the root of the game, and
+    /// can't point outside of it.
+    pub fn open(&self, path: &Path, mode: &str) -> anyhow::Result<Box<dyn ASFile>> {
+        let path = sandbox_path(path)?;
+        let name = archive_name(&path);
+        match self {
+            Self::Directory(c) => {
+                let fname = c.join(&path);
+                confine(c, &fname, mode)?;
+                open_path(&fname, mode)
+            }
+            Self::Archive(c) => {
+                if mode != "r" {
+                    Err(ASFileError::from(&name, mode, FileErrors::ReadOnlySource))?
+                }
+                let archive_error = |e: zip::result::ZipError| {
+                    ASFileError::from(
+                        &name,
+                        mode,
+                        match e {
+                            zip::result::ZipError::FileNotFound => FileErrors::NotFound,
+                            e => FileErrors::ArchiveLoadError(e.to_string()),
+                        },
+                    )
+                };
+                let mut archive =
+                    zip::ZipArchive::new(open_file(c, mode)?).map_err(archive_error)?;
+                let mut contents = vec![];
+                archive
+                    .by_name(&name)
+                    .map_err(archive_error)?
+                    .read_to_end(&mut contents)?;
+                Ok(Box::new(Cursor::new(contents)))
+            }
+            Self::Memory(files) => {
+                if mode != "r" {
+                    Err(ASFileError::from(&name, mode, FileErrors::ReadOnlySource))?
+                }
+                match files.get(&name) {
+                    Some(c) => Ok(Box::new(Cursor::new(c.clone()))),
+                    None => Err(ASFileError::from(&name, mode, FileErrors::NotFound))?,
+                }
+            }
+        }
+    }
+}
+
+/// Normalizes a path relative to the game (or save) folder, and makes sure it doesn't
+/// lead outside of it
+pub(crate) fn sandbox_path(path: &Path) -> anyhow::Result<PathBuf> {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(c) => out.push(c),
+            Component::CurDir => (),
+            Component::ParentDir => {
+                if !out.pop() {
+                    Err(ASFileError::from(
+                        &path.to_string_lossy(),
+                        "",
+                        FileErrors::ForbiddenPath,
+                    ))?
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => Err(ASFileError::from(
+                &path.to_string_lossy(),
+                "",
+                FileErrors::ForbiddenPath,
+            ))?,
+        }
+    }
+    Ok(out)
+}
+
+/// Makes sure `fname` stays inside `root` once symlinks are followed. Since files might not
+/// exist yet when writing, this checks the nearest ancestor that does.
+pub(crate) fn confine(root: &Path, fname: &Path, mode: &str) -> anyhow::Result<()> {
+    if !resolve_links(fname)?.starts_with(resolve_links(root)?) {
+        Err(ASFileError::from(
+            &fname.to_string_lossy(),
+            mode,
+            FileErrors::ForbiddenPath,
+        ))?
+    }
+    Ok(())
+}
+
+/// Canonicalizes the nearest existing ancestor of a path, and adds the rest of it back
+fn resolve_links(path: &Path) -> anyhow::Result<PathBuf> {
+    let path = std::env::current_dir()?.join(path);
+    let mut missing = vec![];
+    let mut current = path.as_path();
+    // symlink_metadata so a dangling symlink counts as existing and fails to canonicalize
+    while current.symlink_metadata().is_err() {
+        match (current.parent(), current.file_name()) {
+            (Some(parent), Some(name)) => {
+                missing.push(name);
+                current = parent;
+            }
+            _ => return Ok(path.clone()),
+        }
+    }
+    let mut out = current.canonicalize()?;
+    out.extend(missing.iter().rev());
+    Ok(out)
+}
+
+/// Turns a relative path into the `/`-separated form used inside archives and `Memory` sources
+fn archive_name(path: &Path) -> String {
+    let mut out = vec![];
+    for component in path.components() {
+        if let Component::Normal(c) = component {
+            out.push(c.to_string_lossy().to_string());
+        }
+    }
+    out.join("/")
+}
+
+/// Opens a file on the actual filesystem
+pub(crate) fn open_path(fname: &Path, mode: &str) -> anyhow::Result<Box<dyn ASFile>> {
+    Ok(Box::new(open_file(fname, mode)?))
+}
+
+fn open_file(fname: &Path, mode: &str) -> anyhow::Result<File> {
+    if mode == "w" {
+        if let Some(c) = fname.parent() {
+            if !c.is_dir() {
+                std::fs::create_dir_all(c)?
+            }
+        }
+    }
+
+    //this manages std::io errors
+    let return_errors = |i: std::io::Result<File>| -> anyhow::Result<File> {
+        match i {
+            Ok(c) => Ok(c),
+            Err(e) => {
+                use std::io::ErrorKind as EK;
+                match e.kind() {
+                    EK::NotFound => Err(ASFileError::from(
+                        &fname.to_string_lossy(),
+                        mode,
+                        FileErrors::NotFound,
+                    ))?,
+                    EK::PermissionDenied => Err(ASFileError::from(
+                        &fname.to_string_lossy(),
+                        mode,
+                        FileErrors::MissingPermissions,
+                    ))?,
+
+                    _ => Err(e)?,
+                }
+            }
+        }
+    };
+
+    match mode {
+        "r" => return_errors(File::open(fname)),
+        "w" => return_errors(File::create(fname)),
+        _ => Err(ASFileError::from(
+            &fname.to_string_lossy(),
+            mode,
+            FileErrors::InvalidMode(mode.to_string()),
+        ))?,
+    }
+}
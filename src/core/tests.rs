@@ -0,0 +1,940 @@
+use crate::core::{
+    error::{ASFileError, FileErrors},
+    ASEvent, ASVariable, AdventureIO, FileType, GameInfo, GameSource, KeyVar, Limits,
+};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    convert::TryFrom,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+const INFO: &str = "name = \"Test\"\ninternal_name = \"test\"\nversion = \"1.0.0\"\n";
+
+/// A temporary folder only used by one test, deleted when dropped
+pub(crate) struct TestDir(PathBuf);
+
+impl TestDir {
+    pub(crate) fn new() -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "adventure_script_test_{}_{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    /// Creates a game folder with a start script, and `config` added to a basic `info.toml`
+    pub(crate) fn game(script: &str, config: &str) -> Self {
+        let dir = Self::new();
+        dir.write("info.toml", &format!("{}{}", INFO, config));
+        dir.write("script/start.as2", script);
+        dir
+    }
+
+    pub(crate) fn join(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.0.join(path)
+    }
+
+    pub(crate) fn write(&self, path: &str, contents: &str) {
+        let path = self.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    /// Creates a portable game from this folder, with its config loaded
+    pub(crate) fn info(&self, io: AdventureIO, debug: bool) -> GameInfo {
+        let mut info = GameInfo::create(GameSource::Directory(self.0.clone()), io, true, debug);
+        info.load_config().unwrap();
+        info
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn memory_game() -> GameInfo {
+    GameInfo::create(
+        GameSource::from_memory(HashMap::from([
+            ("info.toml".to_string(), INFO),
+            ("script/start.as2".to_string(), "Hello!\n!ending"),
+        ])),
+        AdventureIO::default(),
+        true,
+        false,
+    )
+}
+
+#[test]
+fn memory_source() {
+    let mut info = memory_game();
+    info.load_config().unwrap();
+    assert_eq!(info.config.as_ref().unwrap().internal_name, "test");
+    info.load_script(None).unwrap();
+    assert_eq!(info.get_line().unwrap(), "Hello!");
+    assert!(info.load_script(Some("other")).is_err());
+    assert!(info.load_file("info.toml", "w", FileType::Other).is_err());
+}
+
+#[test]
+fn archive_source() {
+    let dir = TestDir::new();
+    let path = dir.join("game.zip");
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+    zip.start_file("script/start.as2", Default::default())
+        .unwrap();
+    zip.write_all(b"In a zip").unwrap();
+    zip.finish().unwrap();
+
+    let info = GameInfo::create(
+        GameSource::from_path(path.clone()),
+        AdventureIO::default(),
+        true,
+        false,
+    );
+    let mut text = String::new();
+    info.load_file("start.as2", "r", FileType::Script)
+        .unwrap()
+        .read_to_string(&mut text)
+        .unwrap();
+    assert_eq!(text, "In a zip");
+    assert!(info.load_file("other.as2", "r", FileType::Script).is_err());
+}
+
+#[test]
+fn sandboxed_paths() {
+    let info = memory_game();
+    for path in ["../../etc/passwd", "/etc/passwd", "script/../../info.toml"] {
+        let err = info.load_file(path, "r", FileType::Other).err().unwrap();
+        assert!(matches!(
+            err.downcast_ref::<ASFileError>(),
+            Some(ASFileError {
+                details: FileErrors::ForbiddenPath,
+                ..
+            })
+        ));
+    }
+    assert!(info
+        .load_file("script/../info.toml", "r", FileType::Other)
+        .is_ok());
+    assert!(info
+        .load_file(
+            "../info.toml",
+            "r",
+            FileType::CustomDir("script".to_string())
+        )
+        .is_ok());
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinked_paths() {
+    let dir = TestDir::new();
+    let outside = dir.join("outside");
+    let game = dir.join("game");
+    std::fs::create_dir_all(&outside).unwrap();
+    std::fs::create_dir_all(&game).unwrap();
+    std::os::unix::fs::symlink(&outside, game.join("link")).unwrap();
+    std::fs::create_dir_all(game.join("save")).unwrap();
+    std::os::unix::fs::symlink(&outside, game.join("save/link")).unwrap();
+
+    let info = GameInfo::create(
+        GameSource::Directory(game),
+        AdventureIO::default(),
+        true,
+        false,
+    );
+    for (name, ftype) in [
+        ("new.txt", FileType::CustomDir("link".to_string())),
+        ("sub/new.txt", FileType::CustomDir("link".to_string())),
+        ("link/save.ad2", FileType::Save),
+    ] {
+        let err = info.load_file(name, "w", ftype).err().unwrap();
+        assert!(matches!(
+            err.downcast_ref::<ASFileError>(),
+            Some(ASFileError {
+                details: FileErrors::ForbiddenPath,
+                ..
+            })
+        ));
+    }
+    assert_eq!(std::fs::read_dir(&outside).unwrap().count(), 0);
+}
+
+#[test]
+fn limits() {
+    let mut info = memory_game();
+    info.limits = Limits {
+        max_steps: 10,
+        max_size: 100,
+        ..Limits::default()
+    };
+    for _ in 0..10 {
+        info.count_step().unwrap();
+    }
+    assert!(info.count_step().is_err());
+    info.wait().unwrap_or(());
+    info.count_step().unwrap();
+
+    let string = ASVariable::String("abcd".to_string());
+    assert!(string.clone().mul_limited(ASVariable::Int(25), 100).is_ok());
+    assert!(string.mul_limited(ASVariable::Int(i64::MAX), 100).is_err());
+    let list = ASVariable::List(vec![]);
+    assert_eq!(
+        list.mul_limited(ASVariable::Int(i64::MAX), 100).unwrap(),
+        ASVariable::List(vec![])
+    );
+}
+
+#[test]
+fn host_api() {
+    let mut info = memory_game();
+    info.set_var_value("gold", 10).unwrap();
+    info.set_var_value("items", vec!["sword", "shield"])
+        .unwrap();
+    assert_eq!(info.get_var_value::<i64>("gold").unwrap(), 10);
+    assert_eq!(
+        info.get_var_value::<Vec<String>>("items").unwrap(),
+        vec!["sword".to_string(), "shield".to_string()]
+    );
+    assert!(info.get_var_value::<bool>("gold").is_err());
+    assert!(info.get_var_value::<i64>("silver").is_err());
+
+    assert!(!info.get_flag("met_king"));
+    info.set_flag("met_king", true);
+    assert!(info.get_flag("met_king"));
+
+    let map = HashMap::from([(KeyVar::from("a"), 1)]);
+    assert_eq!(
+        HashMap::<KeyVar, i64>::try_from(ASVariable::from(map.clone())).unwrap(),
+        map
+    );
+    assert_eq!(ASVariable::from(None::<bool>), ASVariable::None);
+}
+
+#[test]
+fn current_label() {
+    let mut info = GameInfo::create(
+        GameSource::from_memory(HashMap::from([(
+            "script/start.as2".to_string(),
+            "Intro\n{first}\nText\n{second}\nMore text",
+        )])),
+        AdventureIO::default(),
+        true,
+        false,
+    );
+    info.load_script(None).unwrap();
+    assert_eq!(info.current_label(), None);
+    info.pointer = 2;
+    assert_eq!(info.current_label(), Some("first"));
+    info.pointer = 4;
+    assert_eq!(info.current_label(), Some("second"));
+}
+
+#[test]
+fn events() {
+    let events = Rc::new(RefCell::new(vec![]));
+    let mut info = memory_game();
+    let events_ = events.clone();
+    info.add_listener(Box::new(move |_info, event| {
+        events_.borrow_mut().push(event.clone())
+    }));
+    info.load_script(None).unwrap();
+    info.set_var_value("gold", 10).unwrap();
+    info.del_var(&ASVariable::VarRef {
+        name: "gold".to_string(),
+        flag: false,
+    })
+    .unwrap();
+    assert_eq!(
+        *events.borrow(),
+        vec![
+            ASEvent::ScriptLoaded("start".to_string()),
+            ASEvent::VarChanged {
+                name: "gold".to_string(),
+                flag: false,
+                value: ASVariable::Int(10)
+            },
+            ASEvent::VarDeleted {
+                name: "gold".to_string(),
+                flag: false
+            },
+        ]
+    );
+}
+
+#[test]
+fn achievements() {
+    use crate::formats::{achievements::load_achievements, persistent};
+
+    let dir = TestDir::game("Hello!", "\n[[module]]\nname = \"achievements\"\n");
+    dir.write(
+        "achievements.toml",
+        "[[achievement]]\nid = \"start\"\nname = \"Getting started\"\n\n\
+        [[achievement]]\nid = \"secret\"\nname = \"Secret\"\nhidden = true\n",
+    );
+
+    let create = || {
+        let mut info = dir.info(AdventureIO::default().with_unlock(|_| Ok(())), false);
+        persistent::load(&mut info).unwrap();
+        info.achievements = load_achievements(&info).unwrap();
+        info
+    };
+
+    let mut info = create();
+    let events = Rc::new(RefCell::new(vec![]));
+    let events_ = events.clone();
+    info.add_listener(Box::new(move |_, event| {
+        events_.borrow_mut().push(event.clone())
+    }));
+    assert!(info.unlock_achievement("start").unwrap());
+    assert!(!info.unlock_achievement("start").unwrap());
+    assert!(info.unlock_achievement("nope").is_err());
+    assert_eq!(
+        events.borrow().as_slice(),
+        [ASEvent::AchievementUnlocked("start".to_string())]
+    );
+
+    // unlocks are kept in a new game
+    let info = create();
+    let progress = info.achievement_progress();
+    assert_eq!(progress.len(), 2);
+    assert!(progress[0].1 && !progress[1].1);
+}
+
+#[test]
+fn title_screen() {
+    let mut info = GameInfo::create(
+        GameSource::from_memory(HashMap::from([
+            (
+                "info.toml".to_string(),
+                "name = \"Test\"\ninternal_name = \"test\"\nversion = \"1.0.0\"",
+            ),
+            ("script/start.as2".to_string(), "Hello!\n!ending"),
+            ("script/title.as2".to_string(), "Title\n!newgame"),
+        ])),
+        AdventureIO::default(),
+        true,
+        false,
+    );
+    info.load_config().unwrap();
+    info.title_screen().unwrap();
+    assert_eq!(info.script_name(), "title");
+    assert_eq!(info.get_line().unwrap(), "Title");
+
+    info.set_flag("old", true);
+    info.set_var_value("gold", 10).unwrap();
+    info.new_game().unwrap();
+    assert_eq!(info.script_name(), "start");
+    assert_eq!(info.pointer(), 1);
+    assert!(!info.get_flag("old"));
+    assert!(info.get_var_value::<i64>("gold").is_err());
+}
+
+#[test]
+fn choice_events() {
+    let events = Rc::new(RefCell::new(vec![]));
+    let mut info = memory_game();
+    info.io = AdventureIO::default_with(None, None, Some(|| Ok("1".to_string())), None, None, None);
+    let events_ = events.clone();
+    info.add_listener(Box::new(move |_, event| {
+        if matches!(
+            event,
+            ASEvent::ChoicePresented { .. } | ASEvent::ChoiceMade { .. }
+        ) {
+            events_.borrow_mut().push(event.clone())
+        }
+    }));
+    info.load_config().unwrap();
+    // the title menu is part of the engine, not of the game
+    info.title_screen().unwrap();
+    assert_eq!(info.script_name(), "start");
+    assert!(events.borrow().is_empty());
+
+    assert_eq!(info.query("Where?", vec!["Left", "Right"]).unwrap(), 1);
+    assert_eq!(
+        *events.borrow(),
+        vec![
+            ASEvent::ChoicePresented {
+                text: "Where?".to_string(),
+                choices: vec!["Left".to_string(), "Right".to_string()]
+            },
+            ASEvent::ChoiceMade {
+                number: 1,
+                text: "Left".to_string()
+            },
+        ]
+    );
+}
+
+#[test]
+fn endings() {
+    use crate::formats::persistent;
+
+    let dir = TestDir::game(
+        "Hello!",
+        "\n[[ending]]\nid = \"good\"\ntitle = \"Good ending\"\n\n\
+        [[ending]]\nid = \"secret\"\ntitle = \"Secret ending\"\nhidden = true\n",
+    );
+
+    let create = |debug| {
+        let mut info = dir.info(AdventureIO::default(), debug);
+        persistent::load(&mut info).unwrap();
+        info
+    };
+
+    let mut info = create(true);
+    let ending = info.reach_ending("good").unwrap().unwrap();
+    assert_eq!(ending.title, "Good ending");
+    assert!(info.reach_ending("bad").is_err());
+    assert!(create(false).reach_ending("bad").unwrap().is_none());
+
+    let progress = create(false).ending_progress();
+    assert_eq!(progress.len(), 2);
+    assert!(progress[0].1);
+    assert!(!progress[1].1);
+}
+
+#[test]
+fn new_game_plus() {
+    let mut info = GameInfo::create(
+        GameSource::from_memory(HashMap::from([
+            (
+                "info.toml".to_string(),
+                format!(
+                    "{}\n[new_game_plus]\nflags = [\"met\"]\nvariables = [\"gold\"]\n",
+                    INFO
+                )
+                .as_str(),
+            ),
+            ("script/start.as2".to_string(), "Hello!\n!ending"),
+            ("script/title.as2".to_string(), "Title\n!newgame true"),
+        ])),
+        AdventureIO::default(),
+        true,
+        false,
+    );
+    info.load_config().unwrap();
+    info.new_game().unwrap();
+    info.set_flag("met", true);
+    info.set_flag("other", true);
+    info.set_var_value("gold", 5).unwrap();
+    info.end_playthrough(true).unwrap();
+    assert!(!info.quitting);
+    assert_eq!(info.script_name(), "title");
+
+    info.new_game_plus().unwrap();
+    assert_eq!(info.script_name(), "start");
+    assert!(info.get_flag("met"));
+    assert!(!info.get_flag("other"));
+    assert_eq!(info.get_var_value::<i64>("gold").unwrap(), 5);
+}
+
+#[test]
+fn save_slots() {
+    use crate::formats::save::{self, SaveMeta};
+
+    let dir = TestDir::game("{intro}\nHello!\n!ending", "");
+    let mut info = dir.info(AdventureIO::default(), false);
+    info.new_game().unwrap();
+    info.next_line();
+    info.show("Hello!").unwrap();
+    info.set_var_value("gold", 1).unwrap();
+    save::save_to(&mut info, "1").unwrap();
+    info.set_var_value("gold", 2).unwrap();
+    save::save_to(&mut info, "named").unwrap();
+    assert!(save::save_to(&mut info, "../escape").is_err());
+
+    let saves = save::list_saves(&info).unwrap();
+    assert_eq!(saves.len(), 2);
+    assert_eq!(saves[0].slot, "named");
+    assert_eq!(saves[0].label.as_deref(), Some("intro"));
+    assert_eq!(saves[0].excerpt, "Hello!");
+    assert_eq!(saves[0].game_ver, "1.0.0");
+
+    assert!(save::restore_from(&mut info, "1").unwrap());
+    assert_eq!(info.get_var_value::<i64>("gold").unwrap(), 1);
+    assert_eq!(info.pointer(), 2);
+    save::delete(&info, "1").unwrap();
+    assert_eq!(save::list_saves(&info).unwrap().len(), 1);
+    assert!(!dir.join("save/save_1.ad2").exists());
+
+    let meta = SaveMeta {
+        slot: "1".to_string(),
+        timestamp: 1700000000,
+        playtime: 3720,
+        script: "start".to_string(),
+        label: None,
+        excerpt: "Hello!".to_string(),
+        game_ver: "1.0.0".to_string(),
+    };
+    assert_eq!(
+        meta.describe(),
+        "1 - Hello! (2023-11-14 22:13, played 1h 02m)"
+    );
+}
+
+#[test]
+fn new_save_names() {
+    use crate::formats::save;
+
+    // new save, an invalid name, an existing one that isn't overwritten, and then a new one
+    fn input() -> anyhow::Result<String> {
+        static STEP: AtomicUsize = AtomicUsize::new(0);
+        let inputs = ["2", "my save", "1", "2", "named"];
+        Ok(inputs[STEP.fetch_add(1, Ordering::Relaxed)].to_string())
+    }
+    let dir = TestDir::game("Hello!\n!ending", "");
+    let mut info = dir.info(
+        AdventureIO::default_with(None, None, Some(input), None, None, None),
+        false,
+    );
+    info.new_game().unwrap();
+    info.set_var_value("gold", 1).unwrap();
+    save::save_to(&mut info, "1").unwrap();
+    info.set_var_value("gold", 2).unwrap();
+    save::save(&mut info).unwrap();
+
+    let slots: Vec<String> = save::list_saves(&info)
+        .unwrap()
+        .into_iter()
+        .map(|c| c.slot)
+        .collect();
+    assert_eq!(slots, vec!["named", "1"]);
+    assert!(save::restore_from(&mut info, "1").unwrap());
+    assert_eq!(info.get_var_value::<i64>("gold").unwrap(), 1);
+}
+
+#[test]
+fn broken_save_on_title() {
+    use crate::formats::save;
+
+    // continue from a broken save, and then quit from the title menu again
+    fn input() -> anyhow::Result<String> {
+        static STEP: AtomicUsize = AtomicUsize::new(0);
+        let inputs = ["2", "4"];
+        Ok(inputs[STEP.fetch_add(1, Ordering::Relaxed)].to_string())
+    }
+    let dir = TestDir::game("Hello!\n!ending", "");
+    let mut info = dir.info(
+        AdventureIO::default_with(None, None, Some(input), None, None, None),
+        false,
+    );
+    info.new_game().unwrap();
+    save::save_to(&mut info, "1").unwrap();
+    dir.write("save/save_1.ad2", "not a save");
+
+    info.title_screen().unwrap();
+    assert!(info.quitting);
+}
+
+#[test]
+fn game_over_restore() {
+    use crate::formats::save;
+
+    let dir = TestDir::game("Hello!\n!lose", "");
+    let mut info = dir.info(
+        AdventureIO::default_with(None, None, Some(|| Ok("1".to_string())), None, None, None),
+        false,
+    );
+    info.commands = Rc::new(crate::core::main_commands());
+    info.new_game().unwrap();
+    save::save_to(&mut info, "1").unwrap();
+
+    info.next_line();
+    let commands = info.commands.clone();
+    crate::parsing::parse_line(&mut info, &commands).unwrap();
+    // the main loop moves to the next line after every command
+    info.next_line();
+    assert_eq!(info.get_line().unwrap(), "Hello!");
+}
+
+#[test]
+fn autosave() {
+    use crate::formats::save::{self, AUTOSAVE_SLOT};
+
+    let dir = TestDir::game("Hello!\n!ending", "autosave = 2");
+    let mut info = dir.info(
+        AdventureIO::default_with(None, None, Some(|| Ok("1".to_string())), None, None, None),
+        false,
+    );
+    info.new_game().unwrap();
+    let autosaved = |info: &GameInfo| {
+        save::list_saves(info)
+            .unwrap()
+            .iter()
+            .any(|c| c.slot == AUTOSAVE_SLOT)
+    };
+
+    info.query("", vec!["a"]).unwrap();
+    info.query("", vec!["a"]).unwrap();
+    assert!(!autosaved(&info));
+    info.query("", vec!["a"]).unwrap();
+    assert!(autosaved(&info));
+
+    save::delete(&info, AUTOSAVE_SLOT).unwrap();
+    info.allow_save = false;
+    save::autosave(&mut info).unwrap();
+    assert!(!autosaved(&info));
+}
+
+#[test]
+fn no_saves_in_functions() {
+    use crate::formats::save::{self, AUTOSAVE_SLOT};
+
+    let dir = TestDir::game("!def scene()\n!checkpoint\n!end\n!scene\n!checkpoint", "");
+    let mut info = dir.info(AdventureIO::default(), false);
+    info.commands = Rc::new(crate::core::main_commands());
+    info.new_game().unwrap();
+    let commands = info.commands.clone();
+    let autosaved = |info: &GameInfo| {
+        save::list_saves(info)
+            .unwrap()
+            .iter()
+            .any(|c| c.slot == AUTOSAVE_SLOT)
+    };
+
+    info.pointer = 3;
+    crate::parsing::parse_line(&mut info, &commands).unwrap();
+    assert!(!autosaved(&info));
+    info.next_line();
+    crate::parsing::parse_line(&mut info, &commands).unwrap();
+    assert!(autosaved(&info));
+}
+
+#[test]
+fn undo() {
+    let mut info = GameInfo::create(
+        GameSource::from_memory(HashMap::from([
+            (
+                "info.toml".to_string(),
+                format!("{}undo = 2", INFO).as_str(),
+            ),
+            ("script/start.as2".to_string(), "Hello!\n!ending"),
+        ])),
+        AdventureIO::default_with(None, None, Some(|| Ok("1".to_string())), None, None, None),
+        true,
+        false,
+    );
+    info.load_config().unwrap();
+    info.new_game().unwrap();
+    for gold in 1..=4 {
+        info.set_var_value("gold", gold).unwrap();
+        info.query("", vec!["a"]).unwrap();
+    }
+    info.set_var_value("gold", 5).unwrap();
+
+    assert!(info.undo(1).unwrap());
+    assert_eq!(info.get_var_value::<i64>("gold").unwrap(), 4);
+    assert!(info.undo(1).unwrap());
+    assert_eq!(info.get_var_value::<i64>("gold").unwrap(), 3);
+    // only the last 2 choices were kept
+    assert!(!info.undo(1).unwrap());
+
+    info.allow_undo = false;
+    info.query("", vec!["a"]).unwrap();
+    assert!(!info.undo(1).unwrap());
+}
+
+#[test]
+fn undo_toggle() {
+    let dir = TestDir::game("!undo false\n!undo true\n!ending", "undo = 2");
+    let mut info = dir.info(
+        AdventureIO::default_with(None, None, Some(|| Ok("1".to_string())), None, None, None),
+        false,
+    );
+    info.commands = Rc::new(crate::core::main_commands());
+    info.new_game().unwrap();
+    let commands = info.commands.clone();
+    info.set_var_value("gold", 1).unwrap();
+    info.query("", vec!["a"]).unwrap();
+    info.set_var_value("gold", 2).unwrap();
+
+    crate::parsing::parse_line(&mut info, &commands).unwrap();
+    info.next_line();
+    crate::parsing::parse_line(&mut info, &commands).unwrap();
+    // turning undo back on doesn't bring back the choices from before it was off
+    assert!(!info.undo(1).unwrap());
+    info.query("", vec!["a"]).unwrap();
+    info.set_var_value("gold", 3).unwrap();
+    assert!(info.undo(1).unwrap());
+    assert_eq!(info.get_var_value::<i64>("gold").unwrap(), 2);
+}
+
+#[test]
+fn label_anchored_saves() {
+    use crate::formats::save;
+
+    let dir = TestDir::game("Hello!\n{scene}\nFirst\nSecond\n!ending", "");
+    let mut info = dir.info(AdventureIO::default(), false);
+    info.new_game().unwrap();
+    info.pointer = 3;
+    save::save_to(&mut info, "1").unwrap();
+
+    // lines added before the label
+    dir.write(
+        "script/start.as2",
+        "Hello!\nA new line\nAnother one\n{scene}\nFirst\nSecond\n!ending",
+    );
+    assert!(save::restore_from(&mut info, "1").unwrap());
+    assert_eq!(info.get_line().unwrap(), "Second");
+
+    // lines added between the label and the saved line can't be accounted for, so it warns
+    // and keeps the same offset from the label
+    dir.write(
+        "script/start.as2",
+        "Hello!\n{scene}\nNew\nFirst\nSecond\n!ending",
+    );
+    assert!(save::restore_from(&mut info, "1").unwrap());
+    assert_eq!(info.get_line().unwrap(), "First");
+}
+
+fn encoding_values() -> Vec<(&'static str, ASVariable)> {
+    vec![
+        ("bool", ASVariable::Bool(true)),
+        ("int", ASVariable::Int(-42)),
+        ("string", ASVariable::String("Hello \"there\"".to_string())),
+        (
+            "list",
+            ASVariable::List(vec![
+                ASVariable::Int(1),
+                ASVariable::List(vec![ASVariable::String("nested".to_string())]),
+            ]),
+        ),
+        (
+            "map",
+            ASVariable::Map(HashMap::from([
+                (KeyVar::Int(1), ASVariable::String("one".to_string())),
+                (KeyVar::Bool(false), ASVariable::Int(0)),
+                (
+                    KeyVar::String("inner".to_string()),
+                    ASVariable::Map(HashMap::from([(KeyVar::Int(2), ASVariable::None)])),
+                ),
+            ])),
+        ),
+        ("label", ASVariable::Label(Some("intro".to_string()))),
+        ("no_label", ASVariable::Label(None)),
+        (
+            "varref",
+            ASVariable::VarRef {
+                name: "gold".to_string(),
+                flag: false,
+            },
+        ),
+        ("none", ASVariable::None),
+        (
+            "object",
+            ASVariable::Object {
+                spec: "Point".to_string(),
+                fields: HashMap::from([
+                    ("x".to_string(), ASVariable::Int(1)),
+                    (
+                        "tags".to_string(),
+                        ASVariable::Map(HashMap::from([(
+                            KeyVar::String("a".to_string()),
+                            ASVariable::Bool(true),
+                        )])),
+                    ),
+                ]),
+            },
+        ),
+    ]
+}
+
+#[test]
+fn variable_encoding() {
+    for (name, value) in encoding_values() {
+        let json = serde_json::to_string(&value).unwrap();
+        let decoded: ASVariable = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, value, "{} didn't round-trip", name);
+    }
+}
+
+#[test]
+fn save_encoding() {
+    use crate::formats::save;
+
+    let dir = TestDir::game("Hello!\n!ending", "");
+    let mut info = dir.info(AdventureIO::default(), false);
+    info.new_game().unwrap();
+    for (name, value) in encoding_values() {
+        info.variables.insert(name.to_string(), value);
+    }
+    save::save_to(&mut info, "1").unwrap();
+    info.variables.clear();
+    assert!(save::restore_from(&mut info, "1").unwrap());
+    for (name, value) in encoding_values() {
+        assert_eq!(
+            info.variables.get(name),
+            Some(&value),
+            "{} wasn't restored",
+            name
+        );
+    }
+}
+
+#[test]
+fn legacy_save() {
+    use crate::formats::save::{self, LEGACY_SLOT};
+
+    let dir = TestDir::game("Hello!\nBye!\n!ending", "");
+    // the only save there was in 2.0.0-alpha.3
+    dir.write(
+        "save/save.ad2",
+        r#"{"as_ver":"2.0.0-alpha.3","game_ver":"1.0.0","script":"start","pointer":1,"flags":{},"variables":{"gold":{"Int":5}},"screentext":"Hello!\n"}"#,
+    );
+    let mut info = dir.info(AdventureIO::default(), false);
+    info.commands = Rc::new(crate::core::main_commands());
+    info.new_game().unwrap();
+
+    let saves = save::list_saves(&info).unwrap();
+    assert_eq!(saves.len(), 1);
+    assert_eq!(saves[0].slot, LEGACY_SLOT);
+    assert_eq!(saves[0].excerpt, "Hello!");
+    assert!(!dir.join("save/save.ad2").exists());
+    assert!(save::restore_latest(&mut info).unwrap());
+    assert_eq!(info.get_line().unwrap(), "Bye!");
+    assert_eq!(info.get_var_value::<i64>("gold").unwrap(), 5);
+}
+
+#[test]
+fn save_migrations() {
+    use crate::formats::save;
+
+    let dir = TestDir::game("Hello!\nBye!\n!ending", "");
+    dir.write(
+        "info.toml",
+        "name = \"Test\"\ninternal_name = \"test\"\nversion = \"1.2.0\"",
+    );
+    dir.write(
+        "script/migrations.as2",
+        "!const STEP; 10\n{1.2.0}\n!set bonus; true\n{1.1.0}\n!goto {add}\n!set gold; 0\n{add}\n\
+        !set gold; gold + STEP\n{2.0.0}\n!set gold; 0",
+    );
+    // a save from 2.0.0-alpha.3, from before schema versions, made on version 1.0.0 of the game
+    dir.write(
+        "save/save.ad2",
+        r#"{"as_ver":"2.0.0-alpha.3","game_ver":"1.0.0","script":"start","pointer":1,"flags":{},"variables":{"gold":{"Int":5}},"screentext":"Hello!\n"}"#,
+    );
+    dir.write(
+        "save/save_new.ad2",
+        r#"{"schema": 1000, "as_ver": "2.0.0-alpha.3"}"#,
+    );
+    dir.write(
+        "save/save_newer_game.ad2",
+        r#"{"as_ver": "2.0.0-alpha.3", "game_ver": "1.3.0", "script": "start", "pointer": 1,
+        "flags": {}, "variables": {}, "screentext": ""}"#,
+    );
+
+    let mut info = dir.info(AdventureIO::default(), false);
+    info.commands = Rc::new(crate::core::main_commands());
+    info.new_game().unwrap();
+    let loaded = Rc::new(RefCell::new(vec![]));
+    let loaded_ = loaded.clone();
+    info.add_listener(Box::new(move |_, event| {
+        if let ASEvent::ScriptLoaded(c) = event {
+            loaded_.borrow_mut().push(c.to_string())
+        }
+    }));
+    save::list_saves(&info).unwrap();
+    assert!(save::restore_from(&mut info, save::LEGACY_SLOT).unwrap());
+    assert_eq!(info.script_name(), "start");
+    assert_eq!(info.get_line().unwrap(), "Bye!");
+    assert_eq!(info.get_var_value::<i64>("gold").unwrap(), 15);
+    assert!(info.get_var_value::<bool>("bonus").unwrap());
+    // the migrations script runs in a scope of its own
+    assert!(!info.constants.contains_key("STEP"));
+    assert_eq!(*loaded.borrow(), vec!["start"]);
+
+    let err = save::restore_from(&mut info, "newer_game").unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<ASFileError>(),
+        Some(ASFileError {
+            details: FileErrors::SaveGameTooNew(_),
+            ..
+        })
+    ));
+
+    let err = save::restore_from(&mut info, "new").unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<ASFileError>(),
+        Some(ASFileError {
+            details: FileErrors::SaveSchemaTooNew(1000),
+            ..
+        })
+    ));
+}
+
+#[test]
+fn save_integrity() {
+    use crate::formats::{integrity, persistent, save};
+
+    let dir = TestDir::game("Hello!\n!ending", "save_key = \"secret\"");
+    let create = |debug| {
+        let mut info = dir.info(AdventureIO::default(), debug);
+        info.new_game().unwrap();
+        info
+    };
+    let mut info = create(false);
+    info.set_var_value("gold", 5).unwrap();
+    save::save_to(&mut info, "1").unwrap();
+    assert!(save::restore_from(&mut info, "1").unwrap());
+
+    let path = dir.join("save/save_1.ad2");
+    let file = std::fs::read_to_string(&path).unwrap();
+    std::fs::write(&path, file.replace("{\"Int\":5}", "{\"Int\":500}")).unwrap();
+    let err = save::restore_from(&mut info, "1").unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<ASFileError>(),
+        Some(ASFileError {
+            details: FileErrors::SaveModified,
+            ..
+        })
+    ));
+
+    // debug mode only warns
+    let mut info = create(true);
+    assert!(save::restore_from(&mut info, "1").unwrap());
+    assert_eq!(info.get_var_value::<i64>("gold").unwrap(), 500);
+
+    // persistent data from before the key was added is accepted until the game's files are
+    // marked as signed, and signed right away
+    std::fs::remove_file(dir.join("save/signed.ad2")).unwrap();
+    let path = dir.join("save/persistent.ad2");
+    std::fs::write(&path, r#"{"runs": {"Int": 1}}"#).unwrap();
+    let mut info = create(false);
+    persistent::load(&mut info).unwrap();
+    assert_eq!(info.get_persistent("runs"), Some(&ASVariable::Int(1)));
+    let file = std::fs::read_to_string(&path).unwrap();
+    std::fs::write(&path, file.replace("{\"Int\":1}", "{\"Int\":100}")).unwrap();
+    assert!(persistent::load(&mut info).is_err());
+
+    // after that, files without a signature are rejected, even if they look like older ones
+    integrity::mark_signed(&info).unwrap();
+    std::fs::write(&path, r#"{"runs": {"Int": 100}}"#).unwrap();
+    dir.write(
+        "save/save.ad2",
+        r#"{"as_ver":"2.0.0-alpha.3","game_ver":"1.0.0","script":"start","pointer":1,"flags":{},"variables":{},"screentext":""}"#,
+    );
+    for err in [
+        persistent::load(&mut info).unwrap_err(),
+        save::list_saves(&info).unwrap_err(),
+    ] {
+        assert!(matches!(
+            err.downcast_ref::<ASFileError>(),
+            Some(ASFileError {
+                details: FileErrors::SaveModified,
+                ..
+            })
+        ));
+    }
+}
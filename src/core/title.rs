@@ -0,0 +1,125 @@
+use crate::{
+    core::{
+        error::{ASFileError, FileErrors},
+        FileType, GameInfo,
+    },
+    formats::save,
+};
+
+impl GameInfo {
+    /// Shows the title screen. If the game has a `title.as2` script, it's loaded so it runs as
+    /// the title screen, otherwise the built-in one is shown.
+    pub fn title_screen(&mut self) -> anyhow::Result<()> {
+        match self.load_file("title.as2", "r", FileType::Script) {
+            Ok(_) => return self.load_script(Some("title")),
+            Err(e) => match e.downcast_ref::<ASFileError>() {
+                Some(ASFileError {
+                    details: FileErrors::NotFound,
+                    ..
+                }) => (),
+                _ => Err(e)?,
+            },
+        }
+
+        self.title_menu()
+    }
+
+    /// Ends the current playthrough, going back to the title screen, or quitting if the game
+    /// doesn't have one. If the playthrough was completed, the flags and variables chosen for
+    /// New Game+ are kept for the next one.
+    pub fn end_playthrough(&mut self, completed: bool) -> anyhow::Result<()> {
+        let config = self.config.as_ref().unwrap();
+        if let (true, Some(plus)) = (completed, &config.new_game_plus) {
+            let flags = plus
+                .flags
+                .iter()
+                .filter_map(|c| Some((c.to_string(), self.flags.get(c)?.clone())))
+                .collect();
+            let variables = plus
+                .variables
+                .iter()
+                .filter_map(|c| Some((c.to_string(), self.variables.get(c)?.clone())))
+                .collect();
+            self.carried_over = Some((flags, variables));
+        }
+        if !config.title_screen {
+            self.quit();
+            return Ok(());
+        }
+        self.title_screen()?;
+        // the main loop moves to the next line after running a command
+        if !self.quitting {
+            self.pointer -= 1;
+        }
+        Ok(())
+    }
+
+    /// Starts a New Game+, with the flags and variables kept from the last completed
+    /// playthrough. If there isn't one, it's the same as a new game.
+    pub fn new_game_plus(&mut self) -> anyhow::Result<()> {
+        let (flags, variables) = self.carried_over.clone().unwrap_or_default();
+        self.new_game()?;
+        self.flags.extend(flags);
+        self.variables.extend(variables);
+        Ok(())
+    }
+
+    fn title_menu(&mut self) -> anyhow::Result<()> {
+        let config = self.config.as_ref().unwrap();
+        let mut header = format!("{}\nv{}", config.name, config.version);
+        if let Some(c) = &config.description {
+            header += &format!("\n\n{}", c);
+        }
+
+        loop {
+            let mut choices = vec!["New Game"];
+            if self.carried_over.is_some() {
+                choices.push("New Game+");
+            }
+            if save::save_exists(self) {
+                choices.push("Continue");
+                choices.push("Load");
+            }
+            if !self.achievements.is_empty() {
+                choices.push("Achievements");
+            }
+            if !self.endings().is_empty() {
+                choices.push("Endings");
+            }
+            choices.push("Quit");
+
+            self.show(&header)?;
+            let choice = self.menu("", choices.clone())?;
+            if self.quitting {
+                return Ok(());
+            }
+            let restored = match choices.get((choice as usize).wrapping_sub(1)) {
+                Some(&"New Game") => return self.new_game(),
+                Some(&"New Game+") => return self.new_game_plus(),
+                Some(&"Continue") => save::restore_latest(self),
+                Some(&"Load") => save::restore(self),
+                Some(&"Achievements") => {
+                    self.show_achievements()?;
+                    self.wait()?;
+                    Ok(false)
+                }
+                Some(&"Endings") => {
+                    self.show_endings()?;
+                    self.wait()?;
+                    Ok(false)
+                }
+                Some(&"Quit") => {
+                    self.quit();
+                    return Ok(());
+                }
+                _ => Ok(false),
+            };
+            // a broken save shouldn't lock the player out of the game
+            match restored {
+                Ok(true) => return Ok(()),
+                Ok(false) => (),
+                Err(e) => self.warn(format!("Couldn't restore the save: {:#}", e)),
+            }
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use crate::{
+    core::{ASEvent, GameInfo},
+    formats::save,
+};
+
+impl GameInfo {
+    /// Stores the state of the game from before a choice was made, so the choice can be undone.
+    /// The oldest states are dropped once there are more than the game's `undo` limit.
+    pub(crate) fn record_choice(&mut self, state: save::Save) {
+        let limit = self.config.as_ref().map_or(0, |c| c.undo);
+        self.history.push_back(state);
+        while self.history.len() > limit {
+            self.history.pop_front();
+        }
+    }
+
+    /// Goes back to the choice made `steps` choices ago, as it was before making it. Returns
+    /// false if there aren't that many choices to undo.
+    pub fn undo(&mut self, steps: usize) -> anyhow::Result<bool> {
+        if steps == 0 || steps > self.history.len() {
+            return Ok(false);
+        }
+        let state = self
+            .history
+            .drain(self.history.len() - steps..)
+            .next()
+            .unwrap();
+        save::load_state(self, state, "undo")?;
+        self.show_screentext()?;
+        self.emit(ASEvent::ChoicesUndone(steps));
+        Ok(true)
+    }
+}
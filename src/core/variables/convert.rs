@@ -0,0 +1,160 @@
+use super::{ASType, ASVariable, KeyVar};
+use crate::core::error::ASVarError;
+use std::{collections::HashMap, convert::TryFrom};
+
+// Rust types -> ASVariable
+
+impl From<bool> for ASVariable {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<i64> for ASVariable {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<String> for ASVariable {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl From<&str> for ASVariable {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+impl<T: Into<ASVariable>> From<Vec<T>> for ASVariable {
+    fn from(value: Vec<T>) -> Self {
+        Self::List(value.into_iter().map(|c| c.into()).collect())
+    }
+}
+
+impl<T: Into<ASVariable>> From<HashMap<KeyVar, T>> for ASVariable {
+    fn from(value: HashMap<KeyVar, T>) -> Self {
+        Self::Map(value.into_iter().map(|(k, v)| (k, v.into())).collect())
+    }
+}
+
+impl<T: Into<ASVariable>> From<Option<T>> for ASVariable {
+    fn from(value: Option<T>) -> Self {
+        match value {
+            Some(c) => c.into(),
+            None => Self::None,
+        }
+    }
+}
+
+impl From<KeyVar> for ASVariable {
+    fn from(value: KeyVar) -> Self {
+        value.get()
+    }
+}
+
+// ASVariable -> Rust types
+
+fn wrong_type(expected: ASType, value: &ASVariable) -> anyhow::Error {
+    ASVarError::WrongType {
+        expected,
+        given: value.get_type(),
+    }
+    .into()
+}
+
+impl TryFrom<ASVariable> for bool {
+    type Error = anyhow::Error;
+    fn try_from(value: ASVariable) -> anyhow::Result<Self> {
+        match value {
+            ASVariable::Bool(c) => Ok(c),
+            _ => Err(wrong_type(ASType::Bool, &value)),
+        }
+    }
+}
+
+impl TryFrom<ASVariable> for i64 {
+    type Error = anyhow::Error;
+    fn try_from(value: ASVariable) -> anyhow::Result<Self> {
+        match value {
+            ASVariable::Int(c) => Ok(c),
+            _ => Err(wrong_type(ASType::Int, &value)),
+        }
+    }
+}
+
+impl TryFrom<ASVariable> for String {
+    type Error = anyhow::Error;
+    fn try_from(value: ASVariable) -> anyhow::Result<Self> {
+        match value {
+            ASVariable::String(c) => Ok(c),
+            _ => Err(wrong_type(ASType::String, &value)),
+        }
+    }
+}
+
+impl<T> TryFrom<ASVariable> for Vec<T>
+where
+    T: TryFrom<ASVariable>,
+    anyhow::Error: From<T::Error>,
+{
+    type Error = anyhow::Error;
+    fn try_from(value: ASVariable) -> anyhow::Result<Self> {
+        match value {
+            ASVariable::List(c) => c.into_iter().map(|c| Ok(T::try_from(c)?)).collect(),
+            _ => Err(wrong_type(ASType::List, &value)),
+        }
+    }
+}
+
+impl<T> TryFrom<ASVariable> for HashMap<KeyVar, T>
+where
+    T: TryFrom<ASVariable>,
+    anyhow::Error: From<T::Error>,
+{
+    type Error = anyhow::Error;
+    fn try_from(value: ASVariable) -> anyhow::Result<Self> {
+        match value {
+            ASVariable::Map(c) => c
+                .into_iter()
+                .map(|(k, v)| Ok((k, T::try_from(v)?)))
+                .collect(),
+            _ => Err(wrong_type(ASType::Map, &value)),
+        }
+    }
+}
+
+// KeyVar <-> Rust types
+
+impl From<bool> for KeyVar {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<i64> for KeyVar {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<String> for KeyVar {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl From<&str> for KeyVar {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+impl TryFrom<ASVariable> for KeyVar {
+    type Error = anyhow::Error;
+    fn try_from(value: ASVariable) -> anyhow::Result<Self> {
+        KeyVar::new(value)
+    }
+}
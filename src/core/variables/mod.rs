@@ -6,8 +6,32 @@ use std::{
     hash::Hash,
 };
 
+mod convert;
 mod operations;
 
+/// (De)serializes maps as lists of `[key, value]` pairs, since formats like JSON only allow
+/// strings as map keys
+mod map_entries {
+    use super::{ASVariable, KeyVar};
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::collections::HashMap;
+
+    pub fn serialize<S: Serializer>(
+        map: &HashMap<KeyVar, ASVariable>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(map.iter())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<KeyVar, ASVariable>, D::Error> {
+        Ok(Vec::<(KeyVar, ASVariable)>::deserialize(deserializer)?
+            .into_iter()
+            .collect())
+    }
+}
+
 /// Enum listing all possible types for AdventureScript variables. To see what each type means, check
 /// the `ASVariable` documentation.
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -34,14 +58,17 @@ impl Display for ASType {
 
 impl ASType {
     /// Returns the default value for a variable of the specified type:
-    /// 
+    ///
     /// - Any / None /  VarRef / Label => None
     ///     * For VarRef and Label, trying to use this value before it is initialized
-    ///     will result in an error
+    ///       will result in an error
     /// - Bool => false
     /// - Int => 0
     /// - String => ""
     /// - List / Map => empty list or map
+    /// - Object => an object with no fields
+    ///     * Use [GameInfo::default_for_type](crate::core::GameInfo::default_for_type) to get
+    ///       its fields initialized
     pub fn default_for_type(&self) -> ASVariable {
         match self {
             Self::Any | Self::None | Self::VarRef | Self::Label => ASVariable::None,
@@ -50,11 +77,32 @@ impl ASType {
             Self::String => ASVariable::String("".to_string()),
             Self::List => ASVariable::List(vec![]),
             Self::Map => ASVariable::Map(HashMap::new()),
-            Self::Object(c) => todo!(), //add object initializer??
+            Self::Object(c) => ASVariable::Object {
+                spec: c.to_string(),
+                fields: HashMap::new(),
+            },
         }
     }
 }
 
+impl ASType {
+    /// Gets the type with the given name, as written in scripts (`Int`, `String`...).
+    /// Object types aren't included, since they depend on what's been loaded.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "Any" => Self::Any,
+            "Bool" => Self::Bool,
+            "Int" => Self::Int,
+            "String" => Self::String,
+            "List" => Self::List,
+            "Map" => Self::Map,
+            "Label" => Self::Label,
+            "None" => Self::None,
+            _ => return None,
+        })
+    }
+}
+
 /// Enum used to handle AdventureScript variables.
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum ASVariable {
@@ -69,7 +117,7 @@ pub enum ASVariable {
     /// List value (vector of other `ASVariable`s of any type)
     List(Vec<ASVariable>),
     /// Map value (uses `HashMap`, keys can only be of a type that exists in the `KeyVar` enum)
-    Map(HashMap<KeyVar, ASVariable>),
+    Map(#[serde(with = "map_entries")] HashMap<KeyVar, ASVariable>),
     /// A reference to a label, by name. If `None`, don't jump anywhere, instead continue to the next
     /// line as usual.
     Label(Option<String>),
@@ -108,6 +156,18 @@ impl ASVariable {
             Self::Empty => panic!("Cannot use get_type with Empty type"),
         }
     }
+
+    /// Get the total amount of characters and items in the variable, counting the ones
+    /// inside any nested variables. Used for enforcing [Limits](crate::core::Limits).
+    pub fn size(&self) -> usize {
+        match self {
+            Self::String(c) => c.len(),
+            Self::List(c) => c.iter().map(|c| c.size() + 1).sum(),
+            Self::Map(c) => c.values().map(|c| c.size() + 1).sum(),
+            Self::Object { fields, .. } => fields.values().map(|c| c.size()).sum(),
+            _ => 0,
+        }
+    }
 }
 
 impl Display for ASVariable {
@@ -119,7 +179,7 @@ impl Display for ASVariable {
                 Self::None => "None".to_string(),
                 Self::Bool(c) => if *c { "true" } else { "false" }.to_string(),
                 Self::Int(c) => c.to_string(),
-                Self::String(c) => format!("{}", c),
+                Self::String(c) => c.to_string(),
                 Self::List(c) => format!("[{}]", {
                     let mut out = String::new();
                     let mut first_elem = true;
@@ -1,5 +1,5 @@
 use super::ASVariable;
-use crate::core::error::ASSyntaxError;
+use crate::core::error::{ASLimitError, ASSyntaxError};
 use std::{
     cmp::{Ordering, PartialOrd},
     ops::{Add, Div, Mul, Neg, Not, Sub},
@@ -44,6 +44,24 @@ impl Sub for ASVariable {
 impl Mul for ASVariable {
     type Output = anyhow::Result<Self>;
     fn mul(self, rhs: Self) -> Self::Output {
+        self.mul_limited(rhs, usize::MAX)
+    }
+}
+
+impl ASVariable {
+    /// Same as multiplying, but errors instead of creating a `String` or `List` with a size
+    /// (see [ASVariable::size]) bigger than `max_size`
+    pub fn mul_limited(self, rhs: Self, max_size: usize) -> anyhow::Result<Self> {
+        // check the size before actually allocating anything
+        if let Self::Int(times) = rhs {
+            let size = self.size().saturating_mul(times.max(0) as usize);
+            if size > max_size {
+                Err(ASLimitError::ValueTooBig {
+                    size,
+                    max: max_size,
+                })?
+            }
+        }
         match &self {
             Self::Int(c) => {
                 if let ASVariable::Int(c2) = rhs {
@@ -54,22 +72,17 @@ impl Mul for ASVariable {
             }
             Self::String(c) => {
                 if let ASVariable::Int(c2) = rhs {
-                    let mut result = String::new();
-                    for _ in 0..c2 {
-                        result += &c;
-                    }
-                    Ok(ASVariable::String(result))
+                    Ok(ASVariable::String(c.repeat(c2.max(0) as usize)))
                 } else {
                     op_err("multiply".to_string(), self, rhs)
                 }
             }
             Self::List(c) => {
                 if let ASVariable::Int(c2) = rhs {
-                    let mut result = Vec::new();
-                    for _ in 0..c2 {
-                        result.extend(c.clone());
-                    }
-                    Ok(ASVariable::List(result))
+                    let len = c.len().saturating_mul(c2.max(0) as usize);
+                    Ok(ASVariable::List(
+                        c.iter().cloned().cycle().take(len).collect(),
+                    ))
                 } else {
                     op_err("multiply".to_string(), self, rhs)
                 }
@@ -140,7 +153,7 @@ impl PartialOrd for ASVariable {
 
 fn op_err(op: String, v1: ASVariable, v2: ASVariable) -> anyhow::Result<ASVariable> {
     Err(ASSyntaxError::OperationNotDefined {
-        op: op,
+        op,
         type1: v1.get_type(),
         type2: v2.get_type(),
     })?
@@ -148,7 +161,7 @@ fn op_err(op: String, v1: ASVariable, v2: ASVariable) -> anyhow::Result<ASVariab
 
 fn unary_op_err(op: String, v: ASVariable) -> anyhow::Result<ASVariable> {
     Err(ASSyntaxError::UnaryOperationNotDefined {
-        op: op,
+        op,
         type1: v.get_type(),
     })?
 }
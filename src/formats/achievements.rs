@@ -0,0 +1,121 @@
+use crate::{
+    core::{
+        error::{ASCmdError, ASFileError, CommandErrors, FileErrors},
+        ASEvent, ASVariable, FileType, GameInfo,
+    },
+    formats::persistent,
+};
+use serde_derive::Deserialize;
+use std::{io::Read, path::PathBuf};
+
+/// Key in the persistent variables where unlocked achievements are stored. It has a dot, so
+/// scripts can't change it by accident.
+const UNLOCKED_KEY: &str = "achievements.unlocked";
+
+/// An achievement, as declared in the game's achievements file
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Achievement {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Hidden achievements don't show their name or description until they're unlocked
+    #[serde(default)]
+    pub hidden: bool,
+    pub icon: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Debug)]
+struct AchievementFile {
+    #[serde(default)]
+    achievement: Vec<Achievement>,
+}
+
+/// Loads the achievements of the game, from the file given to the `achievements` module in
+/// `info.toml` (by default, `achievements.toml`). Games without that module have no achievements.
+pub fn load_achievements(info: &GameInfo) -> anyhow::Result<Vec<Achievement>> {
+    let module = match &info.config {
+        Some(c) => c.module.iter().flatten().find(|c| c.name == "achievements"),
+        None => panic!("Config file not initialized"),
+    };
+    let path = match module {
+        Some(c) => c
+            .file
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("achievements.toml")),
+        None => return Ok(vec![]),
+    };
+    let path = path.to_string_lossy().to_string();
+
+    let mut file = String::new();
+    info.load_file(&path, "r", FileType::Other)?
+        .read_to_string(&mut file)?;
+    match toml::from_str::<AchievementFile>(&file) {
+        Ok(c) => Ok(c.achievement),
+        Err(e) => Err(ASFileError::from(
+            &path,
+            "r",
+            FileErrors::ConfigLoadError(e.to_string()),
+        ))?,
+    }
+}
+
+impl GameInfo {
+    /// Gets the IDs of the unlocked achievements
+    pub fn unlocked_achievements(&self) -> Vec<String> {
+        match self.persistent.get(UNLOCKED_KEY) {
+            Some(ASVariable::List(c)) => c.iter().map(|c| c.to_string()).collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Gets every achievement of the game, and whether it's unlocked
+    pub fn achievement_progress(&self) -> Vec<(Achievement, bool)> {
+        let unlocked = self.unlocked_achievements();
+        self.achievements
+            .iter()
+            .map(|c| (c.clone(), unlocked.contains(&c.id)))
+            .collect()
+    }
+
+    /// Shows every achievement to the player, and how many of them are unlocked
+    pub fn show_achievements(&mut self) -> anyhow::Result<()> {
+        let progress = self.achievement_progress();
+        let unlocked = progress.iter().filter(|c| c.1).count();
+        let mut text = format!("Achievements: {}/{}", unlocked, progress.len());
+        for (achievement, unlocked) in progress {
+            text += &if unlocked {
+                format!("\n[x] {} - {}", achievement.name, achievement.description)
+            } else if achievement.hidden {
+                "\n[ ] ???".to_string()
+            } else {
+                format!("\n[ ] {} - {}", achievement.name, achievement.description)
+            };
+        }
+        self.show(&text)
+    }
+
+    /// Unlocks an achievement by ID, and notifies the player if it wasn't unlocked already.
+    /// Returns whether it was just unlocked.
+    pub fn unlock_achievement(&mut self, id: &str) -> anyhow::Result<bool> {
+        let achievement = match self.achievements.iter().find(|c| c.id == id) {
+            Some(c) => c.clone(),
+            None => Err(ASCmdError {
+                command: "achieve".to_string(),
+                details: CommandErrors::UnknownAchievement(id.to_string()),
+            })?,
+        };
+        let mut unlocked = self.unlocked_achievements();
+        if unlocked.contains(&achievement.id) {
+            return Ok(false);
+        }
+        unlocked.push(achievement.id.to_string());
+        self.persistent
+            .insert(UNLOCKED_KEY.to_string(), unlocked.into());
+        persistent::save(self)?;
+
+        self.io.unlock(&achievement)?;
+        self.emit(ASEvent::AchievementUnlocked(achievement.id));
+        Ok(true)
+    }
+}
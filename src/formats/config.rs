@@ -1,6 +1,9 @@
-use crate::core::{
-    error::{ASFileError, FileErrors},
-    FileType, GameInfo,
+use crate::{
+    core::{
+        error::{ASFileError, FileErrors},
+        FileType, GameInfo,
+    },
+    formats::endings::Ending,
 };
 use semver::Version;
 use serde_derive::Deserialize;
@@ -14,9 +17,27 @@ struct UnparsedVerConfig {
     pub description: Option<String>,
     pub version: String,
     pub icon: Option<PathBuf>,
+    #[serde(default = "default_title_screen")]
+    pub title_screen: bool,
+    #[serde(default)]
+    pub ending: Vec<Ending>,
+    pub new_game_plus: Option<NewGamePlus>,
+    #[serde(default)]
+    pub autosave: u32,
+    #[serde(default = "default_undo")]
+    pub undo: usize,
+    pub save_key: Option<String>,
     pub module: Option<Vec<Module>>,
 }
 
+fn default_title_screen() -> bool {
+    true
+}
+
+fn default_undo() -> usize {
+    20
+}
+
 impl UnparsedVerConfig {
     pub fn parse_ver(self) -> anyhow::Result<Config> {
         let version = match Version::parse(&self.version) {
@@ -34,6 +55,12 @@ impl UnparsedVerConfig {
             description: self.description,
             version,
             icon: self.icon,
+            title_screen: self.title_screen,
+            ending: self.ending,
+            new_game_plus: self.new_game_plus,
+            autosave: self.autosave,
+            undo: self.undo,
+            save_key: self.save_key,
             module: self.module,
         })
     }
@@ -46,26 +73,74 @@ pub struct Config {
     pub description: Option<String>,
     pub version: Version,
     pub icon: Option<PathBuf>,
+    /// Whether to show the title screen when the game starts, instead of starting a new game
+    pub title_screen: bool,
+    /// Endings of the game, shown in the endings gallery
+    pub ending: Vec<Ending>,
+    /// Flags and variables kept in a New Game+ after reaching an ending. Without it, there's
+    /// no New Game+.
+    pub new_game_plus: Option<NewGamePlus>,
+    /// Number of choices between autosaves, or 0 to not autosave
+    pub autosave: u32,
+    /// Number of choices that can be undone, or 0 to not allow undoing choices
+    pub undo: usize,
+    /// Key used to sign saves, so saves modified outside of the game are rejected. Without it,
+    /// saves aren't signed.
+    pub save_key: Option<String>,
     pub module: Option<Vec<Module>>,
 }
 
+impl Config {
+    /// Creates a configuration with the basic fields, for games that don't have an `info.toml` file
+    pub fn new(name: &str, internal_name: &str, version: &str) -> anyhow::Result<Self> {
+        UnparsedVerConfig {
+            name: name.to_string(),
+            internal_name: internal_name.to_string(),
+            description: None,
+            version: version.to_string(),
+            icon: None,
+            title_screen: true,
+            ending: vec![],
+            new_game_plus: None,
+            autosave: 0,
+            undo: default_undo(),
+            save_key: None,
+            module: None,
+        }
+        .parse_ver()
+    }
+
+    /// Parses a configuration from the contents of an `info.toml` file
+    pub fn from_toml(text: &str) -> anyhow::Result<Self> {
+        let config: UnparsedVerConfig = match toml::from_str(text) {
+            Ok(c) => c,
+            Err(e) => Err(ASFileError::from(
+                "info.toml",
+                "r",
+                FileErrors::ConfigLoadError(e.to_string()),
+            ))?,
+        };
+        config.parse_ver()
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Module {
     pub name: String,
     pub file: Option<PathBuf>,
 }
 
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct NewGamePlus {
+    #[serde(default)]
+    pub flags: Vec<String>,
+    #[serde(default)]
+    pub variables: Vec<String>,
+}
+
 pub fn load_config(info: &GameInfo) -> anyhow::Result<Config> {
     let mut file = String::from("");
     info.load_file("info.toml", "r", FileType::Other)?
         .read_to_string(&mut file)?;
-    let config: UnparsedVerConfig = match toml::from_str(&file) {
-        Ok(c) => c,
-        Err(e) => Err(ASFileError::from(
-            "info.toml",
-            "r",
-            FileErrors::ConfigLoadError(e.to_string()),
-        ))?,
-    };
-    config.parse_ver()
+    Config::from_toml(&file)
 }
@@ -0,0 +1,92 @@
+use crate::{
+    core::{
+        error::{ASCmdError, CommandErrors},
+        ASVariable, GameInfo,
+    },
+    formats::persistent,
+};
+use serde_derive::Deserialize;
+
+/// Key in the persistent variables where reached endings are stored
+const REACHED_KEY: &str = "endings.reached";
+
+/// An ending, as declared in the game's `info.toml`
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Ending {
+    pub id: String,
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    /// Hidden endings don't show their title or description until they're reached
+    #[serde(default)]
+    pub hidden: bool,
+}
+
+impl GameInfo {
+    /// Gets the endings declared in `info.toml`
+    pub fn endings(&self) -> &[Ending] {
+        match &self.config {
+            Some(c) => &c.ending,
+            None => panic!("Config file not initialized"),
+        }
+    }
+
+    /// Gets the IDs of the endings reached in any playthrough
+    pub fn reached_endings(&self) -> Vec<String> {
+        match self.persistent.get(REACHED_KEY) {
+            Some(ASVariable::List(c)) => c.iter().map(|c| c.to_string()).collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Gets every ending of the game, and whether it was reached
+    pub fn ending_progress(&self) -> Vec<(Ending, bool)> {
+        let reached = self.reached_endings();
+        self.endings()
+            .iter()
+            .map(|c| (c.clone(), reached.contains(&c.id)))
+            .collect()
+    }
+
+    /// Shows every ending to the player, and the percentage of them that was reached
+    pub fn show_endings(&mut self) -> anyhow::Result<()> {
+        let progress = self.ending_progress();
+        let reached = progress.iter().filter(|c| c.1).count();
+        let percentage = match progress.len() {
+            0 => 100,
+            total => reached * 100 / total,
+        };
+        let mut text = format!("Endings: {}/{} ({}%)", reached, progress.len(), percentage);
+        for (ending, reached) in progress {
+            text += &if reached {
+                format!("\n[x] {} - {}", ending.title, ending.description)
+            } else if ending.hidden {
+                "\n[ ] ???".to_string()
+            } else {
+                format!("\n[ ] {} - {}", ending.title, ending.description)
+            };
+        }
+        self.show(&text)
+    }
+
+    /// Marks an ending as reached, and returns it if it's declared in `info.toml`. In debug
+    /// mode, endings that aren't declared are an error, unless the game doesn't declare any.
+    pub fn reach_ending(&mut self, id: &str) -> anyhow::Result<Option<Ending>> {
+        let ending = match self.endings().iter().find(|c| c.id == id) {
+            Some(c) => c.clone(),
+            None if self.debug && !self.endings().is_empty() => Err(ASCmdError {
+                command: "ending".to_string(),
+                details: CommandErrors::UnknownEnding(id.to_string()),
+            })?,
+            None => return Ok(None),
+        };
+        let mut reached = self.reached_endings();
+        if !reached.contains(&ending.id) {
+            reached.push(ending.id.to_string());
+            self.persistent
+                .insert(REACHED_KEY.to_string(), reached.into());
+            persistent::save(self)?;
+        }
+        Ok(Some(ending))
+    }
+}
@@ -0,0 +1,120 @@
+use crate::core::{
+    error::{ASFileError, FileErrors},
+    FileType, GameInfo,
+};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::io::{Read, Write};
+
+/// File in the save folder that marks that the game's files have been signed, after which
+/// unsigned files aren't accepted anymore
+const SIGNED_PATH: &str = "signed.ad2";
+
+/// HMAC-SHA256 of a file's contents with the game's `save_key`
+fn mac(key: &str, content: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes()).unwrap();
+    mac.update(content.as_bytes());
+    mac
+}
+
+/// Computes the signature of a file's contents, as hex
+fn signature(key: &str, content: &str) -> String {
+    mac(key, content)
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|c| format!("{:02x}", c))
+        .collect()
+}
+
+/// Decodes a hex string. Odd lengths fail, since the last pair of digits is incomplete.
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    (0..text.len())
+        .step_by(2)
+        .map(|c| u8::from_str_radix(text.get(c..c + 2)?, 16).ok())
+        .collect()
+}
+
+/// Checks a hex signature in constant time, so the time taken doesn't tell how much of it
+/// was right
+fn matches(key: &str, content: &str, signed: &str) -> bool {
+    match decode_hex(signed.trim()) {
+        Some(c) => mac(key, content).verify_slice(&c).is_ok(),
+        None => false,
+    }
+}
+
+/// Adds a signature line to the contents of a save file, if the game has a `save_key`
+pub(crate) fn sign(info: &GameInfo, content: String) -> String {
+    match info.config.as_ref().and_then(|c| c.save_key.as_ref()) {
+        Some(key) => {
+            let signature = signature(key, &content);
+            format!("{}\n{}", content, signature)
+        }
+        None => content,
+    }
+}
+
+/// Whether a file was written before the game had a `save_key`, and can still be accepted: one
+/// is set now, the file is just JSON without a signature line, and the game's files haven't
+/// been marked as signed yet (see [mark_signed])
+pub(crate) fn is_unsigned(info: &GameInfo, file: &str) -> bool {
+    match info.config.as_ref().and_then(|c| c.save_key.as_ref()) {
+        Some(key) => {
+            !is_marked(info, key) && serde_json::from_str::<serde_json::Value>(file).is_ok()
+        }
+        None => false,
+    }
+}
+
+fn is_marked(info: &GameInfo, key: &str) -> bool {
+    let mut file = String::new();
+    match info.load_file(SIGNED_PATH, "r", FileType::Save) {
+        Ok(mut c) => c.read_to_string(&mut file).is_ok() && matches(key, SIGNED_PATH, &file),
+        Err(_) => false,
+    }
+}
+
+/// Marks the game's files as signed, if it has a `save_key`, so files without a signature are
+/// rejected from then on. It's done once files from before the key are signed, when the game
+/// starts, and whenever a save is written.
+pub(crate) fn mark_signed(info: &GameInfo) -> anyhow::Result<()> {
+    if let Some(key) = info.config.as_ref().and_then(|c| c.save_key.as_ref()) {
+        if !is_marked(info, key) {
+            info.load_file(SIGNED_PATH, "w", FileType::Save)?
+                .write_all(signature(key, SIGNED_PATH).as_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+/// Checks the signature of a save file, if the game has a `save_key`, and returns its contents
+/// without the signature. Modified saves are an error, except in debug mode, where they're
+/// only warned about.
+pub(crate) fn verify<'a>(info: &GameInfo, file: &'a str, path: &str) -> anyhow::Result<&'a str> {
+    let key = match info.config.as_ref().and_then(|c| c.save_key.as_ref()) {
+        Some(c) => c,
+        None => return Ok(file),
+    };
+    // the signature is always on the last line
+    let (content, signed) = match file.trim_end().rsplit_once('\n') {
+        Some((content, signed)) => (content, matches(key, content, signed)),
+        None => (file, false),
+    };
+    if signed {
+        return Ok(content);
+    }
+    if info.debug {
+        info.warn(format!(
+            "save/{} was modified outside of the game, or made without a save key. It's only restored because the game is running in debug mode.",
+            path
+        ));
+        Ok(content)
+    } else {
+        Err(ASFileError::from(
+            &format!("save/{}", path),
+            "r",
+            FileErrors::SaveModified,
+        ))?
+    }
+}
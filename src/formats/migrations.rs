@@ -0,0 +1,113 @@
+use crate::{
+    core::{
+        error::{ASFileError, FileErrors},
+        FileType, GameInfo,
+    },
+    parsing,
+};
+use semver::Version;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// Version of the save format. Increase it, and add a migration, whenever the format changes in
+/// a way older saves can't be read with.
+pub const SAVE_SCHEMA: u64 = 2;
+
+/// Functions converting a save from each schema version to the next one, starting from 1
+const MIGRATIONS: [fn(&mut Map<String, Value>); 1] = [from_v1];
+
+/// Saves from before schema versions were stored. They're missing the position relative to a
+/// label and the playtime, which are filled in with empty values.
+fn from_v1(save: &mut Map<String, Value>) {
+    for (field, value) in [
+        ("label", Value::Null),
+        ("offset", Value::from(0)),
+        ("line_hash", Value::Null),
+        ("playtime", Value::from(0)),
+    ] {
+        save.entry(field).or_insert(value);
+    }
+}
+
+/// Converts a save, as read from its file, to the current schema version
+pub(crate) fn migrate_save(save: &mut Value, save_path: &str) -> anyhow::Result<()> {
+    let save = match save.as_object_mut() {
+        Some(c) => c,
+        None => Err(ASFileError::from(
+            &format!("save/{}", save_path),
+            "r",
+            FileErrors::SaveLoadError("Save isn't a JSON object".to_string()),
+        ))?,
+    };
+    let schema = save.get("schema").and_then(|c| c.as_u64()).unwrap_or(1);
+    if schema > SAVE_SCHEMA {
+        Err(ASFileError::from(
+            &format!("save/{}", save_path),
+            "r",
+            FileErrors::SaveSchemaTooNew(schema),
+        ))?
+    }
+    for migration in &MIGRATIONS[schema.max(1) as usize - 1..] {
+        migration(save);
+    }
+    save.insert("schema".to_string(), Value::from(SAVE_SCHEMA));
+    Ok(())
+}
+
+/// Runs the game's migrations for a save made on an older version of the game. They're in the
+/// `migrations` script, in sections that start with a label named after the version of the
+/// game that needs them (like `{1.2.0}`), and they're run in version order.
+pub(crate) fn migrate_game(info: &mut GameInfo, save_ver: &Version) -> anyhow::Result<()> {
+    let game_ver = match &info.config {
+        Some(c) => c.version.clone(),
+        None => panic!("Config file not initialized"),
+    };
+    if *save_ver >= game_ver {
+        return Ok(());
+    }
+    match info.load_file("migrations.as2", "r", FileType::Script) {
+        Ok(_) => (),
+        Err(e) => match e.downcast_ref::<ASFileError>() {
+            Some(ASFileError {
+                details: FileErrors::NotFound,
+                ..
+            }) => return Ok(()),
+            _ => Err(e)?,
+        },
+    }
+
+    info.with_script("migrations", |info| {
+        run_migrations(info, save_ver, &game_ver)
+    })
+}
+
+fn run_migrations(
+    info: &mut GameInfo,
+    save_ver: &Version,
+    game_ver: &Version,
+) -> anyhow::Result<()> {
+    // other labels can be used inside a section, so only version labels start a new one
+    let mut labels = vec![];
+    let mut lines = 0;
+    while let Some(line) = info.line_at(lines) {
+        let line = line.trim();
+        if line.starts_with('{') && line.ends_with('}') {
+            if let Ok(version) = Version::parse(&line[1..line.len() - 1]) {
+                labels.push((lines, version));
+            }
+        }
+        lines += 1;
+    }
+    let mut sections = vec![];
+    for (c, (start, version)) in labels.iter().enumerate() {
+        if version > save_ver && version <= game_ver {
+            let end = labels.get(c + 1).map_or(lines, |c| c.0);
+            sections.push((version.clone(), *start + 1, end));
+        }
+    }
+    sections.sort();
+    for (_, start, end) in sections {
+        parsing::run_function(info, start, end, HashMap::new())?;
+    }
+    Ok(())
+}
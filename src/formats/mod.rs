@@ -1,2 +1,7 @@
+pub(crate) mod achievements;
 pub(crate) mod config;
+pub(crate) mod endings;
+pub(crate) mod integrity;
+pub(crate) mod migrations;
+pub(crate) mod persistent;
 pub(crate) mod save;
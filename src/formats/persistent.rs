@@ -0,0 +1,58 @@
+use crate::{
+    core::{
+        error::{ASFileError, FileErrors},
+        ASVariable, FileType, GameInfo,
+    },
+    formats::integrity,
+};
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+};
+
+/// File in the save folder where persistent variables are stored, separate from the saves
+const PERSISTENT_PATH: &str = "persistent.ad2";
+
+/// Loads the persistent variables of the game, if they've been stored before
+pub fn load(info: &mut GameInfo) -> anyhow::Result<()> {
+    let mut file = String::new();
+    match info.load_file(PERSISTENT_PATH, "r", FileType::Save) {
+        Ok(c) => c,
+        Err(e) => match e.downcast_ref::<ASFileError>() {
+            Some(ASFileError {
+                details: FileErrors::NotFound,
+                ..
+            }) => return Ok(()),
+            _ => Err(e)?,
+        },
+    }
+    .read_to_string(&mut file)?;
+    // games that add a save_key in an update still have an unsigned file, which is accepted
+    // until the game's files are marked as signed, and signed right away
+    let unsigned = integrity::is_unsigned(info, &file);
+    let file = if unsigned {
+        &file
+    } else {
+        integrity::verify(info, &file, PERSISTENT_PATH)?
+    };
+    info.persistent = match serde_json::from_str::<HashMap<String, ASVariable>>(file) {
+        Ok(c) => c,
+        Err(e) => Err(ASFileError::from(
+            &format!("save/{}", PERSISTENT_PATH),
+            "r",
+            FileErrors::SaveLoadError(e.to_string()),
+        ))?,
+    };
+    if unsigned {
+        save(info)?;
+    }
+    Ok(())
+}
+
+/// Stores the persistent variables of the game
+pub fn save(info: &mut GameInfo) -> anyhow::Result<()> {
+    let data = integrity::sign(info, serde_json::to_string(&info.persistent)?);
+    info.load_file(PERSISTENT_PATH, "w", FileType::Save)?
+        .write_all(data.as_bytes())?;
+    Ok(())
+}
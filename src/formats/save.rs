@@ -1,33 +1,316 @@
-use crate::core::{
-    error::{ASFileError, FileErrors},
-    ASVariable, FileType, GameInfo,
+use crate::{
+    core::{
+        error::{ASFileError, FileErrors},
+        ASEvent, ASVariable, FileType, GameInfo,
+    },
+    formats::{integrity, migrations},
+    parsing,
 };
 use semver::{Version, VersionReq};
 use serde_derive::{Deserialize, Serialize};
 use std::{
+    cmp::Reverse,
     collections::HashMap,
     io::{Read, Write},
     path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct Save {
+    /// Version of the save format, see [migrations::SAVE_SCHEMA]
+    pub schema: u64,
     pub as_ver: String,
     pub game_ver: String,
     pub script: PathBuf,
-    //TODO: save position as a label??
     pub pointer: i64,
+    /// Last label before the saved line, so the position can be found again if lines are added
+    /// or removed before it
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Number of lines between the label and the saved line
+    #[serde(default)]
+    pub offset: i64,
+    /// Hash of the saved line, to check that it didn't change
+    #[serde(default)]
+    pub line_hash: Option<u64>,
     pub flags: HashMap<String, ASVariable>,
     pub variables: HashMap<String, ASVariable>,
     pub screentext: String,
+    /// Data stored by each module, by module name
+    #[serde(default)]
+    pub modules: HashMap<String, ASVariable>,
+    #[serde(default)]
+    pub mod_globals: HashMap<String, ASVariable>,
+    /// `!struct` declarations, in case they're from a script other than the current one
+    #[serde(default)]
+    pub structs: Vec<String>,
+    #[serde(default)]
+    pub script_variables: HashMap<String, ASVariable>,
+    #[serde(default)]
+    pub constants: HashMap<String, ASVariable>,
+    /// Time played until the save was made, in seconds
+    #[serde(default)]
+    pub playtime: u64,
 }
 
+/// Information about a save slot, shown when choosing which save to restore or overwrite
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct SaveMeta {
+    pub slot: String,
+    /// When the save was made, in seconds since the Unix epoch
+    pub timestamp: u64,
+    /// Time played until the save was made, in seconds
+    pub playtime: u64,
+    pub script: String,
+    pub label: Option<String>,
+    /// Last line shown before saving
+    pub excerpt: String,
+    pub game_ver: String,
+}
+
+impl SaveMeta {
+    /// Short description of the save, used in the save and restore menus
+    pub fn describe(&self) -> String {
+        format!(
+            "{} - {} ({}, played {})",
+            self.slot,
+            self.excerpt,
+            format_timestamp(self.timestamp),
+            format_playtime(self.playtime)
+        )
+    }
+}
+
+/// Index of the existing saves and their metadata, so they can be listed without reading
+/// every save file
+const INDEX_PATH: &str = "saves.ad2";
+/// Slot used by `!checkpoint` and by autosaves every few choices
+pub const AUTOSAVE_SLOT: &str = "autosave";
+/// Slot used by quicksaves (`qs` and `ql` at a choice)
+pub const QUICKSAVE_SLOT: &str = "quicksave";
+
+fn slot_path(slot: &str) -> anyhow::Result<String> {
+    if slot.is_empty()
+        || !slot
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        Err(ASFileError::from(
+            &format!("save/save_{}.ad2", slot),
+            "w",
+            FileErrors::InvalidSlot(slot.to_string()),
+        ))?
+    }
+    Ok(format!("save_{}.ad2", slot))
+}
+
+/// Save file used before there were save slots, in 2.0.0-alpha.3 and older
+const LEGACY_PATH: &str = "save.ad2";
+/// Slot that a save from before there were save slots is moved to
+pub const LEGACY_SLOT: &str = "legacy";
+
+/// Lists every save, newest first
+pub fn list_saves(info: &GameInfo) -> anyhow::Result<Vec<SaveMeta>> {
+    import_legacy(info)?;
+    read_index(info)
+}
+
+fn read_index(info: &GameInfo) -> anyhow::Result<Vec<SaveMeta>> {
+    let mut file = String::new();
+    match info.load_file(INDEX_PATH, "r", FileType::Save) {
+        Ok(c) => c,
+        Err(e) => match e.downcast_ref::<ASFileError>() {
+            Some(ASFileError {
+                details: FileErrors::NotFound,
+                ..
+            }) => return Ok(vec![]),
+            _ => Err(e)?,
+        },
+    }
+    .read_to_string(&mut file)?;
+    let mut saves: Vec<SaveMeta> = match serde_json::from_str(&file) {
+        Ok(c) => c,
+        Err(e) => Err(ASFileError::from(
+            &format!("save/{}", INDEX_PATH),
+            "r",
+            FileErrors::SaveLoadError(e.to_string()),
+        ))?,
+    };
+    // the index is kept newest first, which breaks ties between saves made in the same second
+    saves.sort_by_key(|c| Reverse(c.timestamp));
+    Ok(saves)
+}
+
+/// Moves a save from before there were save slots to its own slot, so it's listed and restored
+/// like any other save. It's brought up to date with the save migrations first, since it
+/// doesn't have a schema version.
+pub(crate) fn import_legacy(info: &GameInfo) -> anyhow::Result<()> {
+    let mut file = String::new();
+    match info.load_file(LEGACY_PATH, "r", FileType::Save) {
+        Ok(c) => c,
+        Err(e) => match e.downcast_ref::<ASFileError>() {
+            Some(ASFileError {
+                details: FileErrors::NotFound,
+                ..
+            }) => return Ok(()),
+            _ => Err(e)?,
+        },
+    }
+    .read_to_string(&mut file)?;
+    // these saves were made before save keys existed, so they're never signed, and they're
+    // only accepted until the game's files are marked as signed
+    let file = if integrity::is_unsigned(info, &file) {
+        &file
+    } else {
+        integrity::verify(info, &file, LEGACY_PATH)?
+    };
+    let mut save: serde_json::Value = match serde_json::from_str(file) {
+        Ok(c) => c,
+        Err(e) => Err(ASFileError::from(
+            &format!("save/{}", LEGACY_PATH),
+            "r",
+            FileErrors::SaveLoadError(e.to_string()),
+        ))?,
+    };
+    migrations::migrate_save(&mut save, LEGACY_PATH)?;
+    let save: Save = match serde_json::from_value(save) {
+        Ok(c) => c,
+        Err(e) => Err(ASFileError::from(
+            &format!("save/{}", LEGACY_PATH),
+            "r",
+            FileErrors::SaveLoadError(e.to_string()),
+        ))?,
+    };
+
+    let save_path = slot_path(LEGACY_SLOT)?;
+    let json = integrity::sign(info, serde_json::to_string(&save)?);
+    info.load_file(&save_path, "w", FileType::Save)?
+        .write_all(json.as_bytes())?;
+    let mut saves = read_index(info)?;
+    saves.retain(|c| c.slot != LEGACY_SLOT);
+    saves.insert(
+        0,
+        SaveMeta {
+            slot: LEGACY_SLOT.to_string(),
+            // the time it was made isn't stored, so it's dated when it's imported
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |c| c.as_secs()),
+            playtime: save.playtime,
+            script: save.script.to_string_lossy().to_string(),
+            label: None,
+            excerpt: excerpt(&save.screentext),
+            game_ver: save.game_ver,
+        },
+    );
+    write_index(info, &saves)?;
+    info.io.delete_save(info, LEGACY_PATH)
+}
+
+fn write_index(info: &GameInfo, saves: &[SaveMeta]) -> anyhow::Result<()> {
+    let index = serde_json::to_string(saves)?;
+    info.load_file(INDEX_PATH, "w", FileType::Save)?
+        .write_all(index.as_bytes())?;
+    Ok(())
+}
+
+/// Whether there's a save that can be restored
+pub fn save_exists(info: &GameInfo) -> bool {
+    matches!(list_saves(info), Ok(c) if !c.is_empty())
+}
+
+/// Deletes a save slot. Deleting a slot that doesn't exist does nothing.
+pub fn delete(info: &GameInfo, slot: &str) -> anyhow::Result<()> {
+    let path = slot_path(slot)?;
+    let mut saves = list_saves(info)?;
+    if !saves.iter().any(|c| c.slot == slot) {
+        return Ok(());
+    }
+    saves.retain(|c| c.slot != slot);
+    write_index(info, &saves)?;
+    match info.io.delete_save(info, &path) {
+        Ok(()) => Ok(()),
+        Err(e) => match e.downcast_ref::<ASFileError>() {
+            Some(ASFileError {
+                details: FileErrors::NotFound,
+                ..
+            }) => Ok(()),
+            _ => Err(e),
+        },
+    }
+}
+
+/// Asks the player to pick one of the choices, without letting them save or restore from the
+/// menu itself. Returns the index of the choice, or None if they picked the last one (cancel)
+/// or quit.
+fn pick(info: &mut GameInfo, text: &str, choices: Vec<&str>) -> anyhow::Result<Option<usize>> {
+    match info.menu(text, choices.clone())? as usize {
+        0 => Ok(None),
+        c if c == choices.len() => Ok(None),
+        c => Ok(Some(c - 1)),
+    }
+}
+
+fn confirm(info: &mut GameInfo, text: &str) -> anyhow::Result<bool> {
+    Ok(pick(info, text, vec!["Yes", "No"])? == Some(0))
+}
+
+/// Asks the player which save to restore, or which one to delete, and restores it.
+/// Returns whether a save was restored.
 pub fn restore(info: &mut GameInfo) -> anyhow::Result<bool> {
-    //TODO: multisave
-    let save_path = "save.ad2";
+    let screentext = info.screentext.to_string();
+    loop {
+        let saves = list_saves(info)?;
+        if saves.is_empty() {
+            info.show("No save available")?;
+            return Ok(false);
+        }
+        let descriptions: Vec<String> = saves.iter().map(|c| c.describe()).collect();
+        let mut choices: Vec<&str> = descriptions.iter().map(|c| c.as_str()).collect();
+        choices.push("Delete a save");
+        choices.push("Cancel");
+        match pick(info, "Restore which save?", choices.clone())? {
+            Some(c) if c < saves.len() => return restore_from(info, &saves[c].slot),
+            Some(_) => {
+                choices.remove(saves.len());
+                if let Some(c) = pick(info, "Delete which save?", choices)? {
+                    if confirm(info, &format!("Delete save {}?", saves[c].slot))? {
+                        delete(info, &saves[c].slot)?;
+                    }
+                }
+            }
+            None => {
+                info.screentext = screentext;
+                return Ok(false);
+            }
+        }
+        if info.quitting {
+            return Ok(false);
+        }
+    }
+}
+
+/// Restores the most recent save. Returns whether there was one.
+pub fn restore_latest(info: &mut GameInfo) -> anyhow::Result<bool> {
+    match list_saves(info)?.first() {
+        Some(c) => {
+            let slot = c.slot.to_string();
+            restore_from(info, &slot)
+        }
+        None => {
+            info.show("No save available")?;
+            Ok(false)
+        }
+    }
+}
+
+/// Restores the save in a specific slot. Returns whether the slot had a save.
+pub fn restore_from(info: &mut GameInfo, slot: &str) -> anyhow::Result<bool> {
+    let save_path = slot_path(slot)?;
 
     let mut file = String::from("");
-    match info.load_file(save_path, "r", FileType::Save) {
+    match info.load_file(&save_path, "r", FileType::Save) {
         Ok(c) => c,
         Err(e) => match e.downcast_ref::<ASFileError>() {
             Some(ASFileError {
@@ -41,7 +324,17 @@ pub fn restore(info: &mut GameInfo) -> anyhow::Result<bool> {
         },
     }
     .read_to_string(&mut file)?;
-    let save: Save = match serde_json::from_str(&file) {
+    let file = integrity::verify(info, &file, &save_path)?;
+    let mut save: serde_json::Value = match serde_json::from_str(file) {
+        Ok(c) => c,
+        Err(e) => Err(ASFileError::from(
+            &format!("save/{}", save_path),
+            "r",
+            FileErrors::SaveLoadError(e.to_string()),
+        ))?,
+    };
+    migrations::migrate_save(&mut save, &save_path)?;
+    let save: Save = match serde_json::from_value(save) {
         Ok(c) => c,
         Err(e) => Err(ASFileError::from(
             &format!("save/{}", save_path),
@@ -59,7 +352,7 @@ pub fn restore(info: &mut GameInfo) -> anyhow::Result<bool> {
         ))?,
     };
 
-    if !VersionReq::parse(&format!(">= 2.0.0-alpha.1")) //TODO: update on betas
+    if !VersionReq::parse(">= 2.0.0-alpha.1") //TODO: update on betas
         .unwrap()
         .matches(&ver)
     {
@@ -81,6 +374,42 @@ pub fn restore(info: &mut GameInfo) -> anyhow::Result<bool> {
         ))?
     }
 
+    let game_ver = match Version::parse(&save.game_ver) {
+        Ok(c) => c,
+        Err(e) => Err(ASFileError::from(
+            &format!("save/{}", save_path),
+            "r",
+            FileErrors::SaveLoadError(e.to_string()),
+        ))?,
+    };
+
+    let current_ver = match &info.config {
+        Some(c) => &c.version,
+        None => panic!("Config file not initialized"),
+    };
+    if game_ver > *current_ver {
+        Err(ASFileError::from(
+            &format!("save/{}", save_path),
+            "r",
+            FileErrors::SaveGameTooNew(game_ver.to_string()),
+        ))?
+    }
+
+    info.show("Restored save\n")?;
+    let playtime = Duration::from_secs(save.playtime);
+    load_state(info, save, &save_path)?;
+    migrations::migrate_game(info, &game_ver)?;
+    info.set_playtime(playtime);
+    // choices made before restoring can't be undone
+    info.history.clear();
+    info.show_screentext()?;
+    info.emit(ASEvent::SaveRestored);
+
+    Ok(true)
+}
+
+/// Puts the game in the state stored in a save, or in a snapshot taken for undoing choices
+pub(crate) fn load_state(info: &mut GameInfo, save: Save, save_path: &str) -> anyhow::Result<()> {
     info.load_script(Some(
         match &save.script.as_os_str().to_os_string().into_string() {
             Ok(c) => c,
@@ -94,23 +423,70 @@ pub fn restore(info: &mut GameInfo) -> anyhow::Result<bool> {
         },
     ))?;
 
-    info.show("Restored save\n")?;
+    let pointer = locate(info, &save);
+    // constants from the current script were already declared when loading it
+    for (name, value) in save.constants {
+        info.constants.entry(name).or_insert(value);
+    }
+    for decl in save.structs {
+        parsing::declare_struct(info, decl)?;
+    }
 
-    info.pointer = save.pointer;
+    info.pointer = pointer;
     info.flags = save.flags;
     info.variables = save.variables;
+    info.script_variables = save.script_variables;
     info.screentext = save.screentext;
-    info.show_screentext()?;
+    for (name, value) in save.mod_globals {
+        // globals from modules that aren't loaded anymore are ignored
+        if info.mod_globals.contains_key(&name) {
+            info.mod_globals.insert(name, value);
+        }
+    }
+    info.module_restore(save.modules)
+}
 
-    Ok(true)
+/// Finds the line a save was made on, by its label if it has one. If the line changed since the
+/// save was made, the player is warned, since the game might not continue where it should.
+fn locate(info: &GameInfo, save: &Save) -> i64 {
+    let pointer = match &save.label {
+        Some(c) => match info.label_line(c) {
+            Some(c) => c + save.offset,
+            None => save.pointer,
+        },
+        None => save.pointer,
+    };
+    let hash = match save.line_hash {
+        Some(c) => c,
+        None => return pointer,
+    };
+    if info.line_at(pointer).map(line_hash) == Some(hash) {
+        return pointer;
+    }
+    // the lines after the label might have changed, but not the saved one
+    if info.line_at(save.pointer).map(line_hash) == Some(hash) {
+        return save.pointer;
+    }
+    info.warn(format!(
+        "The script changed since this save was made, so the game might not continue from the same place (line {} of {})",
+        pointer + 1,
+        info.script_name()
+    ));
+    pointer
 }
 
-pub fn save(info: &mut GameInfo) -> anyhow::Result<()> {
-    //TODO: add multisave
-    let save_path = "save.ad2";
+/// FNV-1a hash of a line, ignoring indentation. Unlike std's hashers, it's the same in every
+/// version of Rust, so it can be stored in saves.
+fn line_hash(line: &str) -> u64 {
+    line.trim().bytes().fold(0xcbf29ce484222325, |hash, c| {
+        (hash ^ c as u64).wrapping_mul(0x100000001b3)
+    })
+}
 
-    let screentext = info.screentext.to_string();
-    let save = serde_json::to_string(&Save {
+/// Takes a snapshot of the current state of the game, as it would be saved
+pub(crate) fn snapshot(info: &GameInfo) -> anyhow::Result<Save> {
+    Ok(Save {
+        schema: migrations::SAVE_SCHEMA,
         as_ver: env!("CARGO_PKG_VERSION").to_string(),
         game_ver: match &info.config {
             Some(c) => c.version.to_string(),
@@ -118,15 +494,181 @@ pub fn save(info: &mut GameInfo) -> anyhow::Result<()> {
         },
         script: PathBuf::from(info.script_name()),
         pointer: info.pointer,
+        label: info.current_label_line().map(|c| c.0.to_string()),
+        offset: info
+            .current_label_line()
+            .map_or(info.pointer, |c| info.pointer - c.1),
+        line_hash: info.line_at(info.pointer).map(line_hash),
         flags: info.flags.clone(),
         variables: info.variables.clone(),
-        screentext: screentext.clone(),
+        screentext: info.screentext.to_string(),
+        modules: info.module_save()?,
+        mod_globals: info.mod_globals.clone(),
+        structs: info.structs.clone(),
+        script_variables: info.script_variables.clone(),
+        constants: info.constants.clone(),
+        playtime: info.playtime().as_secs(),
     })
-    .unwrap();
-    info.load_file(save_path, "w", FileType::Save)?
-        .write(save.as_bytes())?;
+}
+
+/// Asks the player which slot to save to, confirming before overwriting a save
+pub fn save(info: &mut GameInfo) -> anyhow::Result<()> {
+    let screentext = info.screentext.to_string();
+    let saves = list_saves(info)?;
+    let descriptions: Vec<String> = saves.iter().map(|c| c.describe()).collect();
+    let mut choices: Vec<&str> = descriptions.iter().map(|c| c.as_str()).collect();
+    choices.push("New save");
+    choices.push("Cancel");
+    let slot = match pick(info, "Save to which slot?", choices)? {
+        Some(c) if c < saves.len() => {
+            if !confirm(info, &format!("Overwrite save {}?", saves[c].slot))? {
+                info.screentext = screentext;
+                return Ok(());
+            }
+            saves[c].slot.to_string()
+        }
+        Some(_) => new_slot_name(info, &saves)?,
+        None => {
+            info.screentext = screentext;
+            return Ok(());
+        }
+    };
+    info.screentext = screentext;
+    save_to(info, &slot)
+}
+
+/// Asks the player for the name of a new save, until it's a valid one that either isn't used
+/// or that they agree to overwrite
+fn new_slot_name(info: &mut GameInfo, saves: &[SaveMeta]) -> anyhow::Result<String> {
+    loop {
+        info.io
+            .show("Name of the new save (leave empty to number it):")?;
+        let slot = match info.io.input()?.trim() {
+            "" => (1..)
+                .map(|c: u32| c.to_string())
+                .find(|c| !saves.iter().any(|s| &s.slot == c))
+                .unwrap(),
+            c => c.to_string(),
+        };
+        if slot_path(&slot).is_err() {
+            info.io
+                .show("Save names can only have letters, numbers, - and _")?;
+        } else if !saves.iter().any(|c| c.slot == slot)
+            || confirm(info, &format!("Overwrite save {}?", slot))?
+        {
+            return Ok(slot);
+        }
+    }
+}
 
+/// Saves the game to a specific slot, replacing the save in it if there's one
+pub fn save_to(info: &mut GameInfo, slot: &str) -> anyhow::Result<()> {
+    write_save(info, slot)?;
+    let screentext = info.screentext.to_string();
     info.show("Saved")?;
     info.screentext = screentext; // so the "Saved." doesn't get added to the screentext
     Ok(())
 }
+
+/// Saves the game to the autosave slot, without telling the player. Does nothing if the game
+/// can't be saved right now (see [GameInfo::can_save]).
+pub fn autosave(info: &mut GameInfo) -> anyhow::Result<()> {
+    if info.can_save() {
+        write_save(info, AUTOSAVE_SLOT)?;
+    }
+    Ok(())
+}
+
+/// Saves the game to the quicksave slot, without asking for a slot. Does nothing if the game
+/// can't be saved right now (see [GameInfo::can_save]).
+pub fn quicksave(info: &mut GameInfo) -> anyhow::Result<()> {
+    if info.can_save() {
+        save_to(info, QUICKSAVE_SLOT)?;
+    }
+    Ok(())
+}
+
+/// Restores the quicksave. Returns whether there was one.
+pub fn quickload(info: &mut GameInfo) -> anyhow::Result<bool> {
+    restore_from(info, QUICKSAVE_SLOT)
+}
+
+fn write_save(info: &mut GameInfo, slot: &str) -> anyhow::Result<()> {
+    let save_path = slot_path(slot)?;
+
+    let save = snapshot(info)?;
+    let json = match serde_json::to_string(&save) {
+        Ok(c) => c,
+        Err(e) => Err(ASFileError::from(
+            &format!("save/{}", save_path),
+            "w",
+            FileErrors::SaveWriteError(e.to_string()),
+        ))?,
+    };
+    let json = integrity::sign(info, json);
+    info.load_file(&save_path, "w", FileType::Save)?
+        .write_all(json.as_bytes())?;
+    integrity::mark_signed(info)?;
+
+    let mut saves = list_saves(info)?;
+    saves.retain(|c| c.slot != slot);
+    saves.insert(
+        0,
+        SaveMeta {
+            slot: slot.to_string(),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |c| c.as_secs()),
+            playtime: save.playtime,
+            script: info.script_name().to_string(),
+            label: info.current_label().map(|c| c.to_string()),
+            excerpt: excerpt(&save.screentext),
+            game_ver: save.game_ver,
+        },
+    );
+    write_index(info, &saves)?;
+
+    info.emit(ASEvent::SaveWritten);
+    Ok(())
+}
+
+/// Last line of the screen text, shortened so it fits in a menu
+fn excerpt(screentext: &str) -> String {
+    let line = screentext
+        .lines()
+        .rev()
+        .map(|c| c.trim())
+        .find(|c| !c.is_empty())
+        .unwrap_or("");
+    if line.chars().count() > 40 {
+        format!("{}...", line.chars().take(37).collect::<String>())
+    } else {
+        line.to_string()
+    }
+}
+
+fn format_playtime(secs: u64) -> String {
+    format!("{}h {:02}m", secs / 3600, secs / 60 % 60)
+}
+
+/// Formats a Unix timestamp as a UTC date, like `2024-01-31 18:05`
+fn format_timestamp(secs: u64) -> String {
+    // days to civil date, from http://howardhinnant.github.io/date_algorithms.html
+    let z = (secs / 86400) as i64 + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        secs / 3600 % 24,
+        secs / 60 % 60
+    )
+}
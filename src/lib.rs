@@ -3,7 +3,9 @@
 //! If you just want to run a game, the [AdventureScriptGame] struct handles all needed processes.
 //! Example:
 //! ```no_run
-//! let mut game = AdventureScriptGame::new("path_to_game".to_string(), None);
+//! use adventure_script::AdventureScriptGame;
+//!
+//! let mut game = AdventureScriptGame::new("path_to_game".to_string(), None, true, false);
 //! game.run();
 //! ```
 //!
@@ -15,13 +17,18 @@ pub mod core;
 pub mod modules;
 
 pub(crate) mod formats;
+
+pub use formats::{achievements::Achievement, config::Config, endings::Ending, save::SaveMeta};
 mod macros;
 mod parsing;
 mod inventory {}
 
-use crate::core::{error::manage_error, main_commands, AdventureIO, CmdSet, GameInfo};
+use crate::core::{
+    error::manage_error, main_commands, ASVariable, AdventureIO, CmdSet, EventListener, GameInfo,
+    GameSource, Limits,
+};
 use semver::Version;
-use std::path::PathBuf;
+use std::{collections::HashMap, convert::TryFrom, path::PathBuf, rc::Rc};
 
 pub fn get_version() -> Version {
     Version::parse(env!("CARGO_PKG_VERSION")).unwrap()
@@ -36,51 +43,132 @@ pub struct AdventureScriptGame {
 impl AdventureScriptGame {
     /// Create a new AdventureScript runner
     ///
-    /// * `root_dir` - Root folder of the game's data
+    /// * `root_dir` - Root folder of the game's data, or path to a zip archive containing it
     /// * `io` - The IO system to use when running the game
     /// * `is_local` - Whether this game is ran as "portable". If true, saves will be stored in
-    /// the ./save folder, otherwise, in AppData, ~/.config, or equivalent
+    ///   the ./save folder, otherwise, in AppData, ~/.config, or equivalent
     /// * `is_debug` - Whether to run the game in debug mode or not. Debug mode shows some
-    /// extra warnings, like deprecations
+    ///   extra warnings, like deprecations
     pub fn new(
         root_dir: String,
         io: Option<AdventureIO>,
         is_local: bool,
         is_debug: bool,
+    ) -> AdventureScriptGame {
+        Self::with_source(
+            GameSource::from_path(PathBuf::from(root_dir)),
+            io,
+            is_local,
+            is_debug,
+        )
+    }
+
+    /// Create a new AdventureScript runner that reads the game's data from the given
+    /// [GameSource]. Arguments are the same as in [AdventureScriptGame::new].
+    pub fn with_source(
+        source: GameSource,
+        io: Option<AdventureIO>,
+        is_local: bool,
+        is_debug: bool,
     ) -> AdventureScriptGame {
         AdventureScriptGame {
-            info: GameInfo::create(
-                PathBuf::from(root_dir),
-                io.unwrap_or_default(),
-                is_local,
-                is_debug,
-            ),
+            info: GameInfo::create(source, io.unwrap_or_default(), is_local, is_debug),
             commands: CmdSet::new(),
         }
     }
 
+    /// Create a new AdventureScript runner for a game defined entirely in code, without an
+    /// `info.toml` file or a `script` folder.
+    ///
+    /// * `config` - The game's configuration, which would usually be loaded from `info.toml`
+    /// * `scripts` - The game's scripts, with the script name (without the `.as2` extension)
+    ///   as the key and its source code as the value. The game starts on the `start` script.
+    ///
+    /// The rest of the arguments are the same as in [AdventureScriptGame::new].
+    ///
+    /// Example:
+    /// ```
+    /// use adventure_script::{AdventureScriptGame, Config};
+    /// use std::collections::HashMap;
+    ///
+    /// let mut config = Config::new("Embedded game", "embedded_game", "1.0.0").unwrap();
+    /// config.title_screen = false;
+    /// let scripts = HashMap::from([("start".to_string(), "Hello!\n!ending".to_string())]);
+    /// let mut game = AdventureScriptGame::from_source(config, scripts, None, true, false);
+    /// game.run();
+    /// ```
+    pub fn from_source(
+        config: Config,
+        scripts: HashMap<String, String>,
+        io: Option<AdventureIO>,
+        is_local: bool,
+        is_debug: bool,
+    ) -> AdventureScriptGame {
+        let source = GameSource::from_memory(
+            scripts
+                .into_iter()
+                .map(|(name, script)| (format!("script/{}.as2", name), script))
+                .collect(),
+        );
+        let mut game = Self::with_source(source, io, is_local, is_debug);
+        game.info.config = Some(config);
+        game
+    }
+
     pub fn run(&mut self) {
-        //load config file
-        if let Err(err) = self.info.load_config() {
-            manage_error(&self.info, err);
-            return;
-        };
+        //load config file, unless it was already given
+        if self.info.config.is_none() {
+            if let Err(err) = self.info.load_config() {
+                manage_error(&mut self.info, err);
+                return;
+            };
+        }
         if self.info.debug {
             println!("AdventureScript v{}\n", env!("CARGO_PKG_VERSION"));
         }
+        //load variables kept from previous playthroughs, and bring older files up to date
+        if let Err(err) = formats::persistent::load(&mut self.info)
+            .and_then(|_| formats::save::import_legacy(&self.info))
+            .and_then(|_| formats::integrity::mark_signed(&self.info))
+        {
+            manage_error(&mut self.info, err);
+            return;
+        };
+        match formats::achievements::load_achievements(&self.info) {
+            Ok(c) => self.info.achievements = c,
+            Err(err) => {
+                manage_error(&mut self.info, err);
+                return;
+            }
+        };
         //add basic commands
         self.commands.extend(main_commands());
-        //load script file
-        if let Err(err) = self.info.load_script(None) {
-            manage_error(&self.info, err);
+        self.info.commands = Rc::new(self.commands.clone());
+        //let modules initialize themselves
+        if let Err(err) = self.info.module_init() {
+            manage_error(&mut self.info, err);
+            return;
+        };
+        //show the title screen, or start the game right away
+        let started = if self.info.config.as_ref().unwrap().title_screen {
+            self.info.title_screen()
+        } else {
+            self.info.new_game()
+        };
+        if let Err(err) = started {
+            manage_error(&mut self.info, err);
             return;
         };
         //main loop
         while !self.info.quitting {
+            if let Err(err) = self.info.count_step() {
+                manage_error(&mut self.info, err);
+                return;
+            }
             match parsing::parse_line(&mut self.info, &self.commands) {
                 Ok(_) => (),
                 Err(err) => {
-                    manage_error(&self.info, err);
+                    manage_error(&mut self.info, err);
                     return;
                 }
             };
@@ -88,6 +176,85 @@ impl AdventureScriptGame {
         }
     }
 
+    /// Set the resource limits for the game. Useful when running untrusted games, or
+    /// for automated playthroughs.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.info.limits = limits;
+    }
+
+    /// Get the value of a variable, converted to a Rust type
+    ///
+    /// ```no_run
+    /// # use adventure_script::AdventureScriptGame;
+    /// # let game = AdventureScriptGame::new("path_to_game".to_string(), None, true, false);
+    /// let gold: i64 = game.get_var("gold").unwrap();
+    /// ```
+    pub fn get_var<T>(&self, name: &str) -> anyhow::Result<T>
+    where
+        T: TryFrom<ASVariable>,
+        anyhow::Error: From<T::Error>,
+    {
+        self.info.get_var_value(name)
+    }
+
+    /// Set the value of a variable, from any Rust type that can be converted into an [ASVariable]
+    pub fn set_var(&mut self, name: &str, value: impl Into<ASVariable>) -> anyhow::Result<()> {
+        self.info.set_var_value(name, value)
+    }
+
+    /// Get the value of a flag. Flags that haven't been set are `false`.
+    pub fn get_flag(&self, name: &str) -> bool {
+        self.info.get_flag(name)
+    }
+
+    /// Set the value of a flag
+    pub fn set_flag(&mut self, name: &str, value: bool) {
+        self.info.set_flag(name, value)
+    }
+
+    /// Name of the script currently being run
+    pub fn current_script(&self) -> &str {
+        self.info.script_name()
+    }
+
+    /// Name of the last label before the line currently being run, if there is one
+    pub fn current_label(&self) -> Option<&str> {
+        self.info.current_label()
+    }
+
+    /// List the game's saves, newest first
+    pub fn list_saves(&self) -> anyhow::Result<Vec<SaveMeta>> {
+        formats::save::list_saves(&self.info)
+    }
+
+    /// Save the game to a slot, replacing the save in it if there's one. Slot names can only
+    /// have letters, numbers, `-` and `_`.
+    pub fn save_to(&mut self, slot: &str) -> anyhow::Result<()> {
+        formats::save::save_to(&mut self.info, slot)
+    }
+
+    /// Restore the save in a slot. Returns whether there was a save in it.
+    pub fn restore_from(&mut self, slot: &str) -> anyhow::Result<bool> {
+        formats::save::restore_from(&mut self.info, slot)
+    }
+
+    /// Go back `steps` choices, to before they were made. Returns false if there aren't that
+    /// many choices to undo.
+    pub fn undo(&mut self, steps: usize) -> anyhow::Result<bool> {
+        self.info.undo(steps)
+    }
+
+    /// Delete the save in a slot
+    pub fn delete_save(&mut self, slot: &str) -> anyhow::Result<()> {
+        formats::save::delete(&self.info, slot)
+    }
+
+    /// Register a function to be called on every event of the game (labels being entered,
+    /// variables changing, choices being made...). See [core::ASEvent] for all events.
+    pub fn add_listener(&mut self, listener: EventListener) {
+        self.info.add_listener(listener);
+    }
+
     pub fn add_module(&mut self, module: modules::Module) {
         //TODO: error if module already exists
         module.add_to(&mut self.info, &mut self.commands);
@@ -1,14 +1,14 @@
 use super::{Module, ObjSpec};
 use crate::{
     command,
-    core::{ASType, KeyVar, TypeMethods},
+    core::{ASType, ASVariable, KeyVar, Method, TypeMethods},
     unwrap_var,
 };
 
 use std::collections::HashMap;
 
-pub fn get_module<'a>(name: Option<&'a str>) -> Module {
-    let name = if let Some(c) = name { c } else { "inv" }.to_string();
+pub fn get_module(name: Option<&str>) -> Module {
+    let name = name.unwrap_or("inv").to_string();
     Module::from(
         name.clone(),
         vec![command! {
@@ -20,7 +20,78 @@ pub fn get_module<'a>(name: Option<&'a str>) -> Module {
         vec![ObjSpec {
             name: "Inventory".to_string(),
             fields: HashMap::from([("inv".to_string(), ASType::Map)]),
-            methods: TypeMethods::new(),
+            defaults: HashMap::new(),
+            methods: TypeMethods::from(
+                vec![
+                    Method::new(
+                        "count".to_string(),
+                        |_info, var, args| {
+                            let key = args[0].clone().as_key()?;
+                            if let ASVariable::Object { fields, .. } = var {
+                                let inv = unwrap_var!(fields -> "inv"; Map)?;
+                                Ok(inv.get(&key).cloned().unwrap_or(ASVariable::Int(0)))
+                            } else {
+                                panic!()
+                            }
+                        },
+                        vec![ASType::Any],
+                        1,
+                        false,
+                    ),
+                    Method::new_mut(
+                        "add".to_string(),
+                        |_info, var, args| {
+                            let key = args[0].clone().as_key()?;
+                            let amount = *unwrap_var!(args -> 1; Option<Int>)?.unwrap_or(&1);
+                            if let ASVariable::Object { fields, .. } = var {
+                                if let Some(ASVariable::Map(inv)) = fields.get_mut("inv") {
+                                    let count = match inv.get(&key) {
+                                        Some(ASVariable::Int(c)) => *c,
+                                        _ => 0,
+                                    };
+                                    inv.insert(key, ASVariable::Int(count + amount));
+                                }
+                                Ok(ASVariable::None)
+                            } else {
+                                panic!()
+                            }
+                        },
+                        vec![ASType::Any, ASType::Int],
+                        1,
+                        false,
+                    ),
+                    Method::new_mut(
+                        "remove".to_string(),
+                        |_info, var, args| {
+                            let key = args[0].clone().as_key()?;
+                            let amount = *unwrap_var!(args -> 1; Option<Int>)?.unwrap_or(&1);
+                            if let ASVariable::Object { fields, .. } = var {
+                                if let Some(ASVariable::Map(inv)) = fields.get_mut("inv") {
+                                    let count = match inv.get(&key) {
+                                        Some(ASVariable::Int(c)) => *c,
+                                        _ => 0,
+                                    };
+                                    // not enough items, leave the inventory as is
+                                    if count < amount {
+                                        return Ok(ASVariable::Bool(false));
+                                    } else if count == amount {
+                                        inv.remove(&key);
+                                    } else {
+                                        inv.insert(key, ASVariable::Int(count - amount));
+                                    }
+                                }
+                                Ok(ASVariable::Bool(true))
+                            } else {
+                                panic!()
+                            }
+                        },
+                        vec![ASType::Any, ASType::Int],
+                        1,
+                        false,
+                    ),
+                ],
+                HashMap::new(),
+            ),
             stringify: |fields| {
                 let inv = unwrap_var!(fields -> "inv"; Map).unwrap();
                 let mut out = "".to_string();
@@ -32,16 +103,16 @@ pub fn get_module<'a>(name: Option<&'a str>) -> Module {
                 }
                 out = out.trim().to_string();
 
-                if out == "" {
+                if out.is_empty() {
                     "Empty!".to_string()
                 } else {
                     out
                 }
             },
         }],
-        HashMap::from([/*(
+        HashMap::from([(
             "global".to_string(),
             ASType::Object(format!("{}.Inventory", name)),
-        )*/]),
+        )]),
     )
 }
@@ -1,4 +1,4 @@
-use crate::core::{ASType, ASVariable, CmdSet, Command, GameInfo, TypeMethods};
+use crate::core::{ASType, ASVariable, CmdSet, Command, EventListener, GameInfo, TypeMethods};
 use std::collections::HashMap;
 
 pub mod inventory;
@@ -8,6 +8,25 @@ pub struct Module {
     pub commands: CmdSet,
     pub objects: Vec<ObjSpec>,
     pub globals: HashMap<String, ASType>,
+    pub listeners: Vec<EventListener>,
+    pub hooks: ModuleHooks,
+}
+
+/// Optional functions a module can use to react to the game's lifecycle
+#[derive(Clone, Default)]
+pub struct ModuleHooks {
+    /// Called once, when the game is started and its config file is loaded
+    pub on_init: Option<fn(&mut GameInfo) -> anyhow::Result<()>>,
+    /// Called every time a new game is started (but not when a save is restored)
+    pub on_game_start: Option<fn(&mut GameInfo) -> anyhow::Result<()>>,
+    /// Called when saving the game. The returned value is stored in the save file, in a section
+    /// only for this module.
+    pub on_save: Option<fn(&GameInfo) -> anyhow::Result<ASVariable>>,
+    /// Called after restoring a save, with the value that `on_save` returned (or `None`, if
+    /// the save has no data for this module)
+    pub on_restore: Option<fn(&mut GameInfo, ASVariable) -> anyhow::Result<()>>,
+    /// Called when the game reaches an ending, with the name of the ending
+    pub on_ending: Option<fn(&mut GameInfo, &str) -> anyhow::Result<()>>,
 }
 
 impl Module {
@@ -23,11 +42,28 @@ impl Module {
             commands: CmdSet::from(commands, aliases),
             objects,
             globals,
+            listeners: vec![],
+            hooks: ModuleHooks::default(),
         }
     }
 
+    /// Sets the lifecycle hooks of the module
+    pub fn with_hooks(mut self, hooks: ModuleHooks) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    /// Adds a function that will be called on every event of the game, see [crate::core::ASEvent]
+    pub fn with_listener(mut self, listener: EventListener) -> Self {
+        self.listeners.push(listener);
+        self
+    }
+
     pub fn add_to(self, info: &mut GameInfo, commands: &mut CmdSet) {
-        info.add_module(self.objects, self.globals, &self.name);
+        info.add_module(self.objects, self.globals, self.hooks, &self.name);
+        for listener in self.listeners {
+            info.add_listener(listener);
+        }
         commands.add_module(self.commands, &self.name)
     }
 }
@@ -37,15 +73,70 @@ pub struct ObjSpec {
     pub name: String,
     pub methods: TypeMethods,
     pub fields: HashMap<String, ASType>,
+    /// Values for fields that shouldn't start with the default value for their type
+    pub defaults: HashMap<String, ASVariable>,
     pub stringify: fn(HashMap<String, ASVariable>) -> String,
 }
 
+impl GameInfo {
+    pub(crate) fn module_init(&mut self) -> anyhow::Result<()> {
+        for (_, hooks) in self.module_hooks.clone() {
+            if let Some(on_init) = hooks.on_init {
+                on_init(self)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn module_game_start(&mut self) -> anyhow::Result<()> {
+        for (_, hooks) in self.module_hooks.clone() {
+            if let Some(on_game_start) = hooks.on_game_start {
+                on_game_start(self)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets the data every module wants stored in the save file, by module name
+    pub(crate) fn module_save(&self) -> anyhow::Result<HashMap<String, ASVariable>> {
+        let mut out = HashMap::new();
+        for (name, hooks) in &self.module_hooks {
+            if let Some(on_save) = hooks.on_save {
+                out.insert(name.to_string(), on_save(self)?);
+            }
+        }
+        Ok(out)
+    }
+
+    pub(crate) fn module_restore(
+        &mut self,
+        mut data: HashMap<String, ASVariable>,
+    ) -> anyhow::Result<()> {
+        for (name, hooks) in self.module_hooks.clone() {
+            if let Some(on_restore) = hooks.on_restore {
+                on_restore(self, data.remove(&name).unwrap_or(ASVariable::None))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn module_ending(&mut self, ending: &str) -> anyhow::Result<()> {
+        for (_, hooks) in self.module_hooks.clone() {
+            if let Some(on_ending) = hooks.on_ending {
+                on_ending(self, ending)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 impl ObjSpec {
     pub fn adapt_for_module(self, module_name: &str) -> Self {
         Self {
             name: format!("{}.{}", module_name, self.name),
             methods: self.methods,
             fields: self.fields,
+            defaults: self.defaults,
             stringify: self.stringify,
         }
     }
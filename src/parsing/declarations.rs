@@ -0,0 +1,202 @@
+use super::{evaluate, parse_line, simplify_brackets, simplify_strings};
+use crate::{
+    core::{
+        error::{ASSyntaxError, ASVarError},
+        ASType, ASVariable, CmdSet, Command, GameInfo, TypeMethods,
+    },
+    modules::ObjSpec,
+};
+use regex::Regex;
+use std::{collections::HashMap, rc::Rc};
+
+/// Registers everything declared in the loaded script (`!struct`s and `!def` functions), so it
+/// can be used before the line that declares it is reached
+pub(crate) fn load_declarations(info: &mut GameInfo) -> anyhow::Result<()> {
+    info.functions = Rc::new(CmdSet::new());
+    let mut c = 0;
+    while let Some(line) = info.line_at(c) {
+        let line = line.trim().to_string();
+        if let Some(decl) = line.strip_prefix("!struct ") {
+            declare_struct(info, decl.to_string())?;
+        } else if let Some(decl) = line.strip_prefix("!def ") {
+            c = declare_function(info, decl, c)?;
+        } else if let Some(decl) = line.strip_prefix("!const ") {
+            declare_const(info, decl.to_string())?;
+        }
+        c += 1;
+    }
+    Ok(())
+}
+
+/// Registers a constant from a `!const` declaration, such as `NAME; value`
+fn declare_const(info: &mut GameInfo, text: String) -> anyhow::Result<()> {
+    let name_regex = Regex::new(r"^[A-Za-z0-9-_]+$")?;
+
+    let (text, strings) = simplify_strings(text)?;
+    let (text, brackets) = simplify_brackets(text)?;
+    let (name, value) = match text.split_once(';') {
+        Some((name, value)) if name_regex.is_match(name.trim()) => (name.trim(), value),
+        _ => Err(ASSyntaxError::InvalidVariableName(text.to_string()))?,
+    };
+    let value = evaluate::expr(info, value.to_string(), &strings, &brackets)?;
+    let value = evaluate::resolve(info, value)?;
+
+    // scripts can be loaded more than once, so only a different value is an error
+    match info.constants.get(name) {
+        Some(c) if *c != value => Err(ASVarError::ConstRedeclared(name.to_string()))?,
+        _ => {
+            info.constants.insert(name.to_string(), value);
+        }
+    }
+    Ok(())
+}
+
+/// Registers a function from a `!def` declaration in the given line, such as
+/// `name(parameter, other_parameter)`. Returns the line the function ends in.
+fn declare_function(info: &mut GameInfo, text: &str, line: i64) -> anyhow::Result<i64> {
+    let def_regex = Regex::new(r"^([A-Za-z0-9_]+)\s*\((.*)\)$")?;
+    let name_regex = Regex::new(r"^[A-Za-z0-9_]+$")?;
+
+    let c = match def_regex.captures(text.trim()) {
+        Some(c) => c,
+        None => Err(ASSyntaxError::InvalidFunction(text.to_string()))?,
+    };
+    let name = c[1].to_string();
+    let mut params = vec![];
+    for param in c[2].split(',').map(|c| c.trim()).filter(|c| !c.is_empty()) {
+        if !name_regex.is_match(param) {
+            Err(ASSyntaxError::InvalidFunction(text.to_string()))?
+        }
+        params.push(param.to_string());
+    }
+    if info.commands.get(&name).is_some() {
+        Err(ASSyntaxError::FunctionIsCommand(name.to_string()))?
+    }
+
+    let mut end = line + 1;
+    loop {
+        match info.line_at(end).map(|c| c.trim()) {
+            Some("!end") => break,
+            Some(c) if c.starts_with("!def ") => {
+                Err(ASSyntaxError::UnclosedFunction(name.to_string()))?
+            }
+            Some(_) => end += 1,
+            None => Err(ASSyntaxError::UnclosedFunction(name.to_string()))?,
+        }
+    }
+
+    Rc::make_mut(&mut info.functions)
+        .commands
+        .push(Command::function(name, params, line + 1, end));
+    Ok(end)
+}
+
+/// Runs the body of a function defined with `!def`, with the given arguments as its parameters,
+/// and gets the value it returns
+pub(crate) fn run_function(
+    info: &mut GameInfo,
+    start: i64,
+    end: i64,
+    args: HashMap<String, ASVariable>,
+) -> anyhow::Result<ASVariable> {
+    info.nested_call(|info| {
+        let commands = Rc::clone(&info.commands);
+        let return_to = info.pointer;
+        info.locals.push(args);
+        info.pointer = start;
+
+        let mut result = Ok(ASVariable::None);
+        while info.pointer < end {
+            if let Err(e) = info.count_step().and_then(|_| parse_line(info, &commands)) {
+                result = Err(e);
+                break;
+            }
+            if let Some(c) = info.returning.take() {
+                result = Ok(c);
+                break;
+            }
+            // a !goto out of the function (or an !ending) stops it, and the game continues from there
+            if info.quitting || info.pointer < start || info.pointer >= end {
+                info.locals.pop();
+                return result;
+            }
+            info.next_line();
+        }
+
+        info.locals.pop();
+        info.pointer = return_to;
+        result
+    })
+}
+
+/// Registers an object type from a `!struct` declaration, such as
+/// `Item; name: String; weight: Int = 1`
+pub(crate) fn declare_struct(info: &mut GameInfo, text: String) -> anyhow::Result<()> {
+    let name_regex = Regex::new(r"^[A-Za-z0-9_]+$")?;
+    let field_regex = Regex::new(r"^([A-Za-z0-9-_]+)\s*:\s*([A-Za-z0-9-_.]+)\s*(=(.*))?$")?;
+
+    let decl = text.trim().trim_end_matches(';').to_string();
+    let (simplified, strings) = simplify_strings(decl.clone())?;
+    let (simplified, brackets) = simplify_brackets(simplified)?;
+    let mut parts = simplified.split(';');
+
+    let name = parts.next().unwrap().trim().to_string();
+    if !name_regex.is_match(&name) {
+        Err(ASSyntaxError::InvalidStruct(decl.to_string()))?
+    }
+
+    let mut fields = HashMap::new();
+    let mut defaults = HashMap::new();
+    for part in parts {
+        let c = match field_regex.captures(part.trim()) {
+            Some(c) => c,
+            None => Err(ASSyntaxError::InvalidStruct(decl.to_string()))?,
+        };
+        let field = c[1].to_string();
+        let type_ = match ASType::from_name(&c[2]) {
+            Some(c) => c,
+            // a struct containing itself would never stop creating its default value
+            None if c[2] == name => Err(ASSyntaxError::InvalidStruct(decl.to_string()))?,
+            None if info.get_object(&c[2]).is_some() => ASType::Object(c[2].to_string()),
+            None => Err(ASSyntaxError::UnknownType(c[2].to_string()))?,
+        };
+        if let Some(value) = c.get(4) {
+            let value = evaluate::expr(info, value.as_str().to_string(), &strings, &brackets)?;
+            let value = evaluate::resolve(info, value)?;
+            if type_ != ASType::Any && type_ != value.get_type() {
+                Err(ASVarError::FieldWrongType {
+                    spec: name.to_string(),
+                    field: field.to_string(),
+                    expected: type_.clone(),
+                    given: value.get_type(),
+                })?
+            }
+            defaults.insert(field.to_string(), value);
+        }
+        fields.insert(field, type_);
+    }
+
+    info.objects.retain(|c| c.name != name);
+    info.objects.push(ObjSpec {
+        name,
+        methods: TypeMethods::new(),
+        fields,
+        defaults,
+        stringify: |fields| {
+            let mut fields = fields.into_iter().collect::<Vec<_>>();
+            fields.sort_by(|a, b| a.0.cmp(&b.0));
+            format!(
+                "{{{}}}",
+                fields
+                    .iter()
+                    .map(|(name, value)| format!("{}: {}", name, value))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        },
+    });
+    if !info.structs.contains(&decl) {
+        info.structs.push(decl);
+    }
+    Ok(())
+}
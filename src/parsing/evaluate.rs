@@ -1,5 +1,8 @@
 use super::{parse_text, simplify_brackets};
-use crate::core::{error::ASSyntaxError, ASVariable, GameInfo, KeyVar, TypeMethods};
+use crate::core::{
+    error::{ASMethodError, ASSyntaxError, ASVarError, MethodErrors},
+    ASType, ASVariable, GameInfo, KeyVar, TypeMethods,
+};
 use regex::{Match, Regex};
 use std::collections::HashMap;
 
@@ -8,11 +11,23 @@ pub fn expr(
     text: String,
     strings: &Vec<String>,
     brackets: &Vec<String>,
+) -> anyhow::Result<ASVariable> {
+    info.nested_expr(|info| expr_(info, text, strings, brackets))
+}
+
+fn expr_(
+    info: &mut GameInfo,
+    text: String,
+    strings: &Vec<String>,
+    brackets: &Vec<String>,
 ) -> anyhow::Result<ASVariable> {
     let text = text.trim();
     let operator_regex = Regex::new(r"\+|-|\*|/|\^|!=|!|==|>=|<=|<|>")?;
-    let mut operators = operator_regex.find_iter(&text).collect::<Vec<Match>>();
-    let raw_vals = operator_regex.split(&text);
+    let mut operators = operator_regex.find_iter(text).collect::<Vec<Match>>();
+    let raw_vals = operator_regex.split(text);
+    let name_regex = Regex::new(r"^[A-Za-z0-9-_]*$")?;
+    let flag_regex = Regex::new(r"^?[A-Za-z0-9-_]*$")?;
+    let method_regex = Regex::new(r"^([A-Za-z0-9-_]+)\s*\(([0-9]+)\)$")?;
 
     let mut values = Vec::<ASVariable>::new();
     for v in raw_vals {
@@ -24,10 +39,51 @@ pub fn expr(
                 Some(_) => methods.push(expr.trim().to_string()),
             }
         }
-        let val = val.unwrap();
+        let mut val = val.unwrap();
+        // Module globals (module.global) and persistent variables (persist.name) look like
+        // a method call without brackets
+        if !methods.is_empty() {
+            let global = format!("{}.{}", val, methods[0]);
+            if info.mod_globals.contains_key(&global)
+                || (val == "persist" && name_regex.is_match(&methods[0]))
+            {
+                val = global;
+                methods.remove(0);
+            }
+        }
+        // Object constructors (Struct(...) or module.Object(...))
+        let mut constructor = None;
+        if let Some(c) = method_regex.captures(&val) {
+            if info.get_object(&c[1]).is_some() {
+                constructor = Some((c[1].to_string(), format!("[{}]", &c[2])));
+            }
+        } else if let Some(c) = methods.first().and_then(|c| method_regex.captures(c)) {
+            let spec = format!("{}.{}", val, &c[1]);
+            if info.get_object(&spec).is_some() {
+                constructor = Some((spec, format!("[{}]", &c[2])));
+                methods.remove(0);
+            }
+        }
+        // Functions defined in the script
+        let functions = info.functions.clone();
+        let mut function = None;
+        if let Some(c) = method_regex.captures(&val) {
+            if let Some(command) = functions.get(&c[1]) {
+                function = Some((command, format!("[{}]", &c[2])));
+            }
+        }
         let parsed: ASVariable;
+        if let Some((spec, bracket)) = constructor {
+            parsed = object(info, spec, bracket, strings, brackets)?;
+        } else if let Some((command, bracket)) = function {
+            let args = match expr(info, bracket, strings, brackets)? {
+                ASVariable::List(c) => c,
+                _ => panic!(),
+            };
+            parsed = command.call(info, args, HashMap::new())?;
+        }
         // Literals
-        if val.parse::<i64>().is_ok() {
+        else if val.parse::<i64>().is_ok() {
             parsed = ASVariable::Int(val.parse::<i64>().unwrap());
         } else if val == "true" || val == "True" {
             parsed = ASVariable::Bool(true);
@@ -57,7 +113,7 @@ pub fn expr(
                     .parse::<usize>()
                     .unwrap();
                 let lname = &brackets[index];
-                if !Regex::new(r"^[A-Za-z0-9-_]*$")?.is_match(lname) {
+                if !name_regex.is_match(lname) {
                     Err(ASSyntaxError::InvalidVariableName(val.to_string()))?;
                 }
                 ASVariable::Label(Some(lname.to_string()))
@@ -68,7 +124,7 @@ pub fn expr(
                 .unwrap();
             let value = parse_text(info, &brackets[index])?;
             let (value, brcks) = simplify_brackets(value)?;
-            parsed = expr(info, value, &strings, &brcks)?;
+            parsed = expr(info, value, strings, &brcks)?;
         } else if val.starts_with("\"") && val.ends_with("\"") {
             let index = ((val.split_at(1).1).split_at(val.len() - 2).0)
                 .parse::<usize>()
@@ -76,22 +132,39 @@ pub fn expr(
             parsed = ASVariable::String(parse_text(info, &strings[index])?);
         } else if val == "None" {
             parsed = ASVariable::None;
-        } else if val == "" {
+        } else if val.is_empty() {
             parsed = ASVariable::Empty;
         }
+        // Module globals and persistent variables
+        else if info.mod_globals.contains_key(&val) || val.starts_with("persist.") {
+            parsed = ASVariable::VarRef {
+                name: val.to_string(),
+                flag: false,
+            }
+        }
+        // Persistent flags
+        else if let Some(name) = val.strip_prefix("??") {
+            if !name_regex.is_match(name) {
+                Err(ASSyntaxError::InvalidVariableName(val.to_string()))?
+            }
+            parsed = ASVariable::VarRef {
+                name: format!("persist.{}", name),
+                flag: true,
+            }
+        }
         //Flags
-        else if val.starts_with("?") {
-            if !Regex::new(r"^?[A-Za-z0-9-_]*$")?.is_match(&val) {
+        else if let Some(name) = val.strip_prefix('?') {
+            if !flag_regex.is_match(&val) {
                 Err(ASSyntaxError::InvalidVariableName(val.to_string()))? //TODO: get proper token content
             }
             parsed = ASVariable::VarRef {
-                name: val[1..].to_string(),
+                name: name.to_string(),
                 flag: true,
             }
         }
         // Variables
         else {
-            if !Regex::new(r"^[A-Za-z0-9-_]*$")?.is_match(&val) {
+            if !name_regex.is_match(&val) {
                 Err(ASSyntaxError::InvalidVariableName(val.to_string()))? //TODO: get proper token content
             }
             parsed = ASVariable::VarRef {
@@ -101,22 +174,23 @@ pub fn expr(
         }
 
         // Can't run methods on an Empty type
-        if methods.len() > 0 && parsed == ASVariable::Empty {
+        if !methods.is_empty() && parsed == ASVariable::Empty {
             Err(ASSyntaxError::InvalidVariableName(val.to_string()))?
         }
         values.push(manage_methods(info, parsed, methods, strings, brackets)?)
     }
     //unary operations
-    for operation in vec!["-", "!"] {
+    for operation in ["-", "!"] {
         let mut c = 0;
         while c < operators.len() {
             // It's a unary operator if the first value is None
             // Yes this is a dumb way to add it shush
             if operators[c].as_str() == operation && values[c] == ASVariable::Empty {
                 operators.remove(c);
+                let value = resolve(info, values[c + 1].clone())?;
                 values[c] = match operation {
-                    "-" => -values[c + 1].clone(),
-                    "!" => !values[c + 1].clone(),
+                    "-" => -value,
+                    "!" => !value,
                     _ => panic!("unrecognized unary operator"),
                 }?;
                 values.remove(c + 1);
@@ -131,10 +205,14 @@ pub fn expr(
         while c < operators.len() {
             if operators[c].as_str() == operation {
                 operators.remove(c);
+                values[c] = resolve(info, values[c].clone())?;
+                values[c + 1] = resolve(info, values[c + 1].clone())?;
                 values[c] = match operation {
                     "+" => values[c].clone() + values[c + 1].clone(),
                     "-" => values[c].clone() - values[c + 1].clone(),
-                    "*" => values[c].clone() * values[c + 1].clone(),
+                    "*" => values[c]
+                        .clone()
+                        .mul_limited(values[c + 1].clone(), info.limits.max_size),
                     "/" => values[c].clone() / values[c + 1].clone(),
                     "^" => values[c].clone().pow(values[c + 1].clone()),
                     "==" => Ok(ASVariable::Bool(values[c].clone() == values[c + 1].clone())),
@@ -145,6 +223,7 @@ pub fn expr(
                     "<=" => Ok(ASVariable::Bool(values[c].clone() <= values[c + 1].clone())),
                     _ => panic!("unrecognized operator"),
                 }?;
+                info.limits.check_size(&values[c])?;
                 values.remove(c + 1);
             } else {
                 c += 1;
@@ -166,12 +245,30 @@ fn manage_methods(
     brackets: &Vec<String>,
 ) -> anyhow::Result<ASVariable> {
     let method_regex = Regex::new("^([A-Za-z0-9-_]+)\\s*\\(([0-9]+)\\)$").unwrap();
+    let field_regex = Regex::new("^[A-Za-z0-9-_]+$").unwrap();
     let mut value = value;
     for method in &methods {
         let method_captures = method_regex.captures(method);
-        if let None = method_captures {
+        if field_regex.is_match(method) {
+            // Object fields
+            value = match value {
+                ASVariable::VarRef { name, flag: false } => ASVariable::VarRef {
+                    name: format!("{}.{}", name, method),
+                    flag: false,
+                },
+                ASVariable::Object { spec, fields } => match fields.get(method) {
+                    Some(c) => c.clone(),
+                    None => Err(ASVarError::UnknownField {
+                        spec,
+                        field: method.to_string(),
+                    })?,
+                },
+                _ => Err(ASSyntaxError::InvalidMethod(method.to_string()))?,
+            }
+        } else if method_captures.is_none() {
             Err(ASSyntaxError::InvalidMethod(method.to_string()))? //TODO: get proper token content
         } else if let Some(c) = method_regex.captures(method) {
+            let type_ = resolve(info, value.clone())?.get_type();
             let bracket = format!("[{}]", c.get(2).unwrap().as_str().parse::<usize>().unwrap());
             let args: Vec<ASVariable>;
             if let ASVariable::List(l) = expr(info, bracket, strings, brackets)? {
@@ -179,7 +276,7 @@ fn manage_methods(
             } else {
                 panic!()
             }
-            value = TypeMethods::get_for_type(info, &value.get_type()).run_method(
+            value = TypeMethods::get_for_type(info, &type_).run_method(
                 c.get(1).unwrap().as_str(),
                 info,
                 &value,
@@ -190,6 +287,57 @@ fn manage_methods(
     Ok(value)
 }
 
+/// Gets the value of variables, leaving any other values as they are
+pub(crate) fn resolve(info: &mut GameInfo, value: ASVariable) -> anyhow::Result<ASVariable> {
+    match value {
+        ASVariable::VarRef { .. } => Ok(info.get_var(&value)?.clone()),
+        c => Ok(c),
+    }
+}
+
+fn object(
+    info: &mut GameInfo,
+    spec: String,
+    bracket: String,
+    strings: &Vec<String>,
+    brackets: &Vec<String>,
+) -> anyhow::Result<ASVariable> {
+    let args = match expr(info, bracket, strings, brackets)? {
+        ASVariable::List(c) => c,
+        _ => panic!(),
+    };
+    let mut values = HashMap::new();
+    match args.len() {
+        0 => (),
+        1 => match resolve(info, args[0].clone())? {
+            ASVariable::Map(c) => {
+                for (key, value) in c {
+                    match key {
+                        KeyVar::String(key) => values.insert(key, resolve(info, value)?),
+                        key => Err(ASVarError::WrongType {
+                            expected: ASType::String,
+                            given: key.get_type(),
+                        })?,
+                    };
+                }
+            }
+            c => Err(ASVarError::WrongType {
+                expected: ASType::Map,
+                given: c.get_type(),
+            })?,
+        },
+        c => Err(ASMethodError {
+            method: spec.to_string(),
+            type_name: spec.to_string(),
+            details: MethodErrors::TooManyArguments {
+                given_args: c,
+                max_args: 1,
+            },
+        })?,
+    }
+    info.new_object(&spec, values)
+}
+
 fn list(info: &mut GameInfo, text: String, strings: &Vec<String>) -> anyhow::Result<ASVariable> {
     if text.trim() == "" {
         return Ok(ASVariable::List(vec![]));
@@ -211,7 +359,7 @@ fn map(info: &mut GameInfo, text: String, strings: &Vec<String>) -> anyhow::Resu
 
     let is_map = Regex::new(":")?;
     for elmt in text.split(",") {
-        match is_map.find(&elmt) {
+        match is_map.find(elmt) {
             Some(c) => {
                 let (key, value) = elmt.split_at(c.start());
                 let key = expr(info, key.to_string(), strings, &brackets)?;
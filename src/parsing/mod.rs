@@ -1,32 +1,41 @@
-use crate::core::{error::ASSyntaxError, ASVariable, CmdSet, GameInfo};
+use crate::core::{error::ASSyntaxError, ASEvent, ASVariable, CmdSet, GameInfo};
 use regex::Regex;
 use std::collections::HashMap;
 
 #[cfg(test)]
 mod tests;
 
+mod declarations;
 mod evaluate;
 
+pub(crate) use declarations::{declare_struct, load_declarations, run_function};
+
 pub fn parse_line(info: &mut GameInfo, commands: &CmdSet) -> anyhow::Result<()> {
     let mut ln = info.get_line()?.to_string();
     while ln.starts_with("!!") {
         info.pointer -= 1;
         ln = info.get_line()?.to_string();
     }
-    if ln.starts_with("#") || (ln.starts_with("{") && ln.trim().ends_with("}")) {
-    } else if ln.starts_with("!") {
+    if ln.starts_with("#") {
+    } else if ln.starts_with("!struct ") || ln.starts_with("!const ") {
+        // declarations are registered when the script is loaded
+    } else if ln.starts_with("!def ") {
+        // function bodies only run when the function is called
+        while info.get_line()?.trim() != "!end" {
+            info.pointer += 1;
+        }
+    } else if ln.starts_with("{") && ln.trim().ends_with("}") {
+        let label = ln.trim();
+        info.emit(ASEvent::LabelEntered(label[1..label.len() - 1].to_string()));
+    } else if let Some(ln) = ln.strip_prefix('!') {
         //TODO: disallow multiline strings
         let mut c = 1;
-        let mut ln = ln[1..].trim().to_owned();
-        loop {
-            let next_ln = match info.line_at(info.pointer + c) {
-                Some(c) => c,
-                None => break,
-            };
-            if next_ln.starts_with("!!>") {
-                ln += &format!("[{}];", next_ln[3..].trim());
-            } else if next_ln.starts_with("!!") {
-                ln += &format!(" {}", next_ln[2..].trim());
+        let mut ln = ln.trim().to_owned();
+        while let Some(next_ln) = info.line_at(info.pointer + c) {
+            if let Some(next_ln) = next_ln.strip_prefix("!!>") {
+                ln += &format!("[{}];", next_ln.trim());
+            } else if let Some(next_ln) = next_ln.strip_prefix("!!") {
+                ln += &format!(" {}", next_ln.trim());
             } else {
                 break;
             }
@@ -51,7 +60,7 @@ pub fn parse_line(info: &mut GameInfo, commands: &CmdSet) -> anyhow::Result<()>
 
 fn parse_text(info: &mut GameInfo, text: &str) -> anyhow::Result<String> {
     let control_code_regex = Regex::new(r"\\(.)(\[([^\[]*|\[.*\])\])?")?;
-    let control_codes = control_code_regex.captures_iter(&text);
+    let control_codes = control_code_regex.captures_iter(text);
 
     let mut text = text.to_string();
     for capture in control_codes {
@@ -97,12 +106,14 @@ fn parse_command(info: &mut GameInfo, commands: &CmdSet, text: String) -> anyhow
     // Get the command from the name
     let split: Vec<&str> = text.split(" ").collect();
 
-    let name = match split.get(0) {
+    let name = match split.first() {
         Some(c) => c,
         None => Err(ASSyntaxError::NoCommand {})?,
     };
 
-    let command = match commands.get(*name) {
+    // functions are shared through an Rc, so running one doesn't need to copy it
+    let functions = info.functions.clone();
+    let command = match commands.get(name).or_else(|| functions.get(name)) {
         Some(c) => c,
         None => Err(ASSyntaxError::NonExistentCommand {
             command: name.to_string(),
@@ -164,16 +175,14 @@ fn simplify_strings(mut text: String) -> anyhow::Result<(String, Vec<String>)> {
 
     // 1.1:   Get all quotes, both single and double
     let mut quotepos = Vec::<usize>::new(); // here we'll store the index of every quote that's not been escaped
-    let mut pos = 0;
     let mut prev_char = '\x00';
-    for chr in text.chars() {
+    for (pos, chr) in text.chars().enumerate() {
         // If the current character is a quote and the character before it isn't a backslash
         // because, well, escaping quotes in strings is A Thing tm
         if (chr == '"' || chr == '\'') && prev_char != '\\' {
             quotepos.push(pos);
         }
 
-        pos += 1;
         prev_char = chr;
     }
 
@@ -218,8 +227,7 @@ fn simplify_strings(mut text: String) -> anyhow::Result<(String, Vec<String>)> {
     quotes.reverse(); // Doing this so it won't mess with the other index values
 
     let mut quotetext = Vec::<String>::new();
-    let mut c = 0;
-    for quote in &quotes {
+    for (c, quote) in quotes.iter().enumerate() {
         quotetext.push(((text.split_at(quote.1).0).split_at(quote.0 + 1).1).to_string());
         text = format!(
             "{}\"{}\"{}",
@@ -227,7 +235,6 @@ fn simplify_strings(mut text: String) -> anyhow::Result<(String, Vec<String>)> {
             quotes.len() - c - 1, // this makes the indexes be in the right order
             text.split_at(quote.1 + 1).1
         );
-        c += 1;
     }
 
     quotetext.reverse();
@@ -289,8 +296,7 @@ fn simplify_brackets(mut text: String) -> anyhow::Result<(String, Vec<String>)>
     brackets.reverse(); // Doing this so it won't mess with the other index values
 
     let mut brackettext = Vec::<String>::new();
-    let mut c = 0;
-    for bracket in &brackets {
+    for (c, bracket) in brackets.iter().enumerate() {
         brackettext.push(((text.split_at(bracket.1).0).split_at(bracket.0 + 1).1).to_string());
         text = format!(
             "{}{}{}",
@@ -298,7 +304,6 @@ fn simplify_brackets(mut text: String) -> anyhow::Result<(String, Vec<String>)>
             brackets.len() - c - 1, // this makes the indexes be in the right order
             text.split_at(bracket.1).1
         );
-        c += 1;
     }
 
     brackettext.reverse();
@@ -1,3 +1,9 @@
+use crate::{
+    core::{main_commands, ASType, AdventureIO, GameInfo, GameSource, KeyVar, Limits},
+    modules::{inventory, Module},
+};
+use std::{collections::HashMap, rc::Rc};
+
 #[test]
 fn simplify_test() {
     let text = "'hel\"lo' \"hel'lo\" 'hel\\'lo' \"hel\\\"lo\"".to_string();
@@ -5,3 +11,291 @@ fn simplify_test() {
     assert_eq!(s, "\"0\" \"1\" \"2\" \"3\"");
     assert_eq!(q, vec!["hel\"lo", "hel'lo", "hel\\'lo", "hel\\\"lo"]);
 }
+
+/// Creates a game with the given scripts (name without extension, contents) and modules, and
+/// loads the first script
+fn load_game(scripts: &[(&str, &str)], modules: Vec<Module>) -> GameInfo {
+    let mut info = GameInfo::create(
+        GameSource::from_memory(
+            scripts
+                .iter()
+                .map(|(name, text)| (format!("script/{}.as2", name), text))
+                .collect(),
+        ),
+        AdventureIO::default(),
+        true,
+        false,
+    );
+    let mut commands = main_commands();
+    for module in modules {
+        module.add_to(&mut info, &mut commands);
+    }
+    info.commands = Rc::new(commands);
+    info.load_script(Some(scripts[0].0)).unwrap();
+    info
+}
+
+/// Loads a start script using the inventory module, and runs its first `lines` lines
+fn run_script(src: &str, lines: usize) -> GameInfo {
+    let mut info = load_game(&[("start", src)], vec![inventory::get_module(None)]);
+    run_lines(&mut info, lines);
+    info
+}
+
+fn run_lines(info: &mut GameInfo, lines: usize) {
+    for _ in 0..lines {
+        step(info).unwrap();
+    }
+}
+
+/// Runs the current line and moves to the next one
+fn step(info: &mut GameInfo) -> anyhow::Result<()> {
+    let commands = info.commands.clone();
+    let result = super::parse_line(info, &commands);
+    info.next_line();
+    result
+}
+
+#[test]
+fn module_globals() {
+    let mut info = load_game(
+        &[(
+            "start",
+            "!set mod.gold; 5\n!set total; mod.gold\n!set mod.gold; \"five\"\n!del mod.gold",
+        )],
+        vec![Module::from(
+            "mod".to_string(),
+            vec![],
+            HashMap::new(),
+            vec![],
+            HashMap::from([("gold".to_string(), ASType::Int)]),
+        )],
+    );
+
+    step(&mut info).unwrap();
+    assert_eq!(info.get_var_value::<i64>("mod.gold").unwrap(), 5);
+    step(&mut info).unwrap();
+    assert_eq!(info.get_var_value::<i64>("total").unwrap(), 5);
+    assert!(step(&mut info).is_err());
+    assert!(step(&mut info).is_err());
+}
+
+#[test]
+fn operators_on_variables() {
+    let mut info = run_script(
+        "!set gold; 5\n!set more; gold + 10\n!set rich; gold > 10\n!set debt; -gold\n!set same; gold == 5\n!set bad; missing + 1",
+        5,
+    );
+    assert_eq!(info.get_var_value::<i64>("more").unwrap(), 15);
+    assert!(!info.get_var_value::<bool>("rich").unwrap());
+    assert_eq!(info.get_var_value::<i64>("debt").unwrap(), -5);
+    assert!(info.get_var_value::<bool>("same").unwrap());
+    assert!(step(&mut info).is_err());
+}
+
+#[test]
+fn objects() {
+    let mut info = run_script(
+        r#"!set bag; inv.Inventory({"inv": {"apple": 2}})
+!set apples; bag.count("apple")
+!set bag.inv; {"pear": 1}
+!set pears; bag.inv.get("pear")
+!set same; bag == inv.Inventory({"inv": {"pear": 1}})
+!set empty; inv.global.inv
+!set bag.inv; 5
+!set bag.weight; 1
+!set bag; inv.Inventory(5)"#,
+        6,
+    );
+    assert_eq!(info.get_var_value::<i64>("apples").unwrap(), 2);
+    assert_eq!(info.get_var_value::<i64>("pears").unwrap(), 1);
+    assert!(info.get_var_value::<bool>("same").unwrap());
+    assert_eq!(
+        info.get_var_value::<HashMap<KeyVar, i64>>("empty").unwrap(),
+        HashMap::new()
+    );
+    for _ in 0..3 {
+        assert!(step(&mut info).is_err());
+    }
+}
+
+#[test]
+fn mutating_methods() {
+    let mut info = run_script(
+        r#"!do inv.global.add("apple", 3)
+!set removed; inv.global.remove("apple")
+!set apples; inv.global.count("apple")
+!set list; [1]
+!do list.append(2)
+!do [1].append(2)"#,
+        5,
+    );
+    assert!(info.get_var_value::<bool>("removed").unwrap());
+    assert_eq!(info.get_var_value::<i64>("apples").unwrap(), 2);
+    assert_eq!(info.get_var_value::<Vec<i64>>("list").unwrap(), vec![1, 2]);
+    assert!(step(&mut info).is_err());
+}
+
+#[test]
+fn append() {
+    let mut info = run_script(
+        r#"!set list; [1]
+!append list; 2
+!set map; {"a": 1}
+!append map; {"b": 2, "a": 3}
+!append map; 4"#,
+        4,
+    );
+    assert_eq!(info.get_var_value::<Vec<i64>>("list").unwrap(), vec![1, 2]);
+    assert_eq!(
+        info.get_var_value::<HashMap<KeyVar, i64>>("map").unwrap(),
+        HashMap::from([
+            (KeyVar::String("a".to_string()), 3),
+            (KeyVar::String("b".to_string()), 2)
+        ])
+    );
+    assert!(step(&mut info).is_err());
+}
+
+#[test]
+fn growing_limits() {
+    let mut info = run_script(
+        r#"!set list; [1, 2]
+!set map; {"a": 1}
+!set text; "ab"
+!append list; 3
+!append map; {"b": 2, "c": 3}
+!add text; "c""#,
+        3,
+    );
+    info.limits = Limits {
+        max_size: 2,
+        ..Limits::default()
+    };
+    for _ in 0..3 {
+        assert!(step(&mut info).is_err());
+    }
+    assert_eq!(info.get_var_value::<Vec<i64>>("list").unwrap(), vec![1, 2]);
+    assert_eq!(info.get_var_value::<String>("text").unwrap(), "ab");
+}
+
+#[test]
+fn structs() {
+    let mut info = load_game(
+        &[
+            (
+                "start",
+                r#"!set sword; Item({"name": "Sword"})
+!set weight; sword.weight
+!set text; sword.str()
+!struct Item; name: String; weight: Int = 1
+!set sword.weight; "heavy""#,
+            ),
+            ("wrong", "!struct Item; weight: Int = \"heavy\""),
+        ],
+        vec![],
+    );
+    run_lines(&mut info, 4);
+    assert_eq!(info.get_var_value::<i64>("weight").unwrap(), 1);
+    assert_eq!(
+        info.get_var_value::<String>("text").unwrap(),
+        "{name: Sword, weight: 1}"
+    );
+    assert!(step(&mut info).is_err());
+    assert!(info.load_script(Some("wrong")).is_err());
+}
+
+#[test]
+fn functions() {
+    let mut info = load_game(
+        &[
+            (
+                "start",
+                r#"!set total; 0
+!def add_twice(n)
+!add total; n * 2
+!end
+!add_twice 3
+!set doubled; double(5)
+!def double(n)
+!return n * 2
+!end
+!set n; 1
+!add_twice
+!return 1"#,
+            ),
+            ("unclosed", "!def broken(n)\n!return n"),
+        ],
+        vec![],
+    );
+    run_lines(&mut info, 6);
+    assert_eq!(info.get_var_value::<i64>("total").unwrap(), 6);
+    assert_eq!(info.get_var_value::<i64>("doubled").unwrap(), 10);
+    // parameters don't replace variables with the same name
+    assert_eq!(info.get_var_value::<i64>("n").unwrap(), 1);
+    assert_eq!(info.pointer(), 11);
+    // missing argument
+    assert!(step(&mut info).is_err());
+    assert!(step(&mut info).is_err());
+    assert!(info.load_script(Some("unclosed")).is_err());
+}
+
+#[test]
+fn scopes() {
+    let mut info = load_game(
+        &[
+            (
+                "start",
+                r#"!const MAX; 10
+!local visits; 1
+!def count()
+!local seen; MAX
+!set visits; visits + 1
+!end
+!count
+!set MAX; 5
+!local MAX; 5
+!loadscript "other""#,
+            ),
+            ("other", "!const MAX; 11"),
+        ],
+        vec![],
+    );
+    run_lines(&mut info, 4);
+    assert_eq!(info.get_var_value::<i64>("MAX").unwrap(), 10);
+    assert_eq!(info.get_var_value::<i64>("visits").unwrap(), 2);
+    // function locals don't exist anymore
+    assert!(info.get_var_value::<i64>("seen").is_err());
+    assert!(step(&mut info).is_err());
+    assert!(step(&mut info).is_err());
+    // script variables are deleted, and constants can't be declared again with another value
+    assert!(step(&mut info).is_err());
+    assert!(info.get_var_value::<i64>("visits").is_err());
+}
+
+#[test]
+fn persistent() {
+    use crate::{
+        core::{tests::TestDir, ASVariable},
+        formats::persistent,
+    };
+
+    let dir = TestDir::game(
+        "!add persist.runs; 1\n!flag ??seen_intro\n!set seen; ??seen_intro",
+        "",
+    );
+    for runs in 1..=2 {
+        let mut info = dir.info(AdventureIO::default(), false);
+        info.commands = Rc::new(main_commands());
+        persistent::load(&mut info).unwrap();
+        if runs == 1 {
+            info.set_persistent("runs", 0).unwrap();
+        }
+        info.load_script(None).unwrap();
+        run_lines(&mut info, 3);
+        assert_eq!(info.get_persistent("runs"), Some(&ASVariable::Int(runs)));
+        assert!(info.get_var_value::<bool>("seen").unwrap());
+        // not stored with the normal flags
+        assert!(!info.get_flag("seen_intro"));
+    }
+}